@@ -0,0 +1,425 @@
+use rstd::vec::Vec;
+use parity_codec::{Decode, Encode};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+use support::{decl_event, decl_module, decl_storage, dispatch::Result, ensure, StorageMap};
+use system::{ensure_root, ensure_signed};
+
+pub trait Trait: system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+// the most claims one order can require
+pub const MAX_CLAIMS: usize = 8;
+// the most operations one eligibility predicate can have
+pub const MAX_PREDICATE_OPS: usize = 16;
+// the longest attribute value a person can declare or be attested for
+pub const MAX_ATTRIBUTE_LEN: usize = 32;
+// the most attestations one account can hold
+pub const MAX_ATTESTATIONS: usize = 16;
+// ISO 3166 codes have at most three letters
+pub const MAX_COUNTRY_LEN: usize = 3;
+
+// Person attributes orders can target.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum Attribute {
+    Region,
+    AgeBand,
+    Occupation,
+}
+
+// What an attester vouches for about an account.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum Claim {
+    // the account belongs to a real person who holds no other attested account
+    UniqueHuman,
+    // the person lives in the country with this ISO 3166 code
+    Country(Vec<u8>),
+    // the person is at least this old
+    AgeAtLeast(u8),
+    // the person's attribute has this value
+    Attribute(Attribute, Vec<u8>),
+}
+
+// One step of an eligibility predicate. A predicate is a list of these in postfix order,
+// evaluated on a stack of booleans, e.g. `[Is(Region, "EU"), Is(AgeBand, "18-25"), And]`.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub enum PredicateOp {
+    // push whether the declared or attested attribute has the value
+    Is(Attribute, Vec<u8>),
+    // push whether an approved attester vouched that the attribute has the value
+    AttestedIs(Attribute, Vec<u8>),
+    // pop two values, push their conjunction
+    And,
+    // pop two values, push their disjunction
+    Or,
+    // pop one value, push its negation
+    Not,
+}
+
+impl Claim {
+    // whether holding this claim is enough to fulfil `required`
+    pub fn satisfies(&self, required: &Claim) -> bool {
+        match (self, required) {
+            (Claim::AgeAtLeast(age), Claim::AgeAtLeast(min)) => age >= min,
+            (claim, required) => claim == required,
+        }
+    }
+
+    // whether the values of the claim stay within their bounds
+    pub fn is_well_formed(&self) -> bool {
+        match self {
+            Claim::Country(code) => !code.is_empty() && code.len() <= MAX_COUNTRY_LEN,
+            Claim::Attribute(_, value) => value.len() <= MAX_ATTRIBUTE_LEN,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Attestation<AccountId, BlockNumber> {
+    attester: AccountId,
+    claim: Claim,
+    issued_at: BlockNumber,
+}
+
+decl_event! {
+    pub enum Event<T>
+    where
+    <T as system::Trait>::AccountId
+    {
+       // attester
+       AttesterAdded(AccountId),
+       // attester
+       AttesterRemoved(AccountId),
+       // attester, subject
+       Attested(AccountId, AccountId),
+       // attester, subject
+       Revoked(AccountId, AccountId),
+       // person
+       AttributeDeclared(AccountId, Attribute),
+    }
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Attestation {
+        // accounts approved by governance to issue attestations
+        pub Attesters get(is_attester): map T::AccountId => bool;
+        // attestations issued for an account
+        pub Attestations get(attestations): map T::AccountId => Vec<Attestation<T::AccountId, T::BlockNumber>>;
+        // the account attested as a unique human, keyed by the attester's private identifier of the person
+        HumanOf get(human_of): map T::Hash => Option<T::AccountId>;
+        // reverse lookup of HumanOf, so a revocation can free the identifier
+        HumanId get(human_id): map T::AccountId => Option<T::Hash>;
+        // how many attesters vouch for a HumanOf binding, it is freed when the last one revokes
+        HumanAttesters get(human_attesters): map T::Hash => u32;
+        // attributes people declared about themselves, keyed by (person, attribute)
+        Declared get(declared): map (T::AccountId, Attribute) => Option<Vec<u8>>;
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        fn deposit_event<T>() = default;
+
+        // Approve an attester, only callable by root (through Sudo).
+        fn add_attester(origin, attester: T::AccountId) -> Result {
+            ensure_root(origin)?;
+            <Attesters<T>>::insert(&attester, true);
+            Self::deposit_event(RawEvent::AttesterAdded(attester));
+            Ok(())
+        }
+
+        // Remove an attester, the attestations it already issued stay valid until revoked.
+        fn remove_attester(origin, attester: T::AccountId) -> Result {
+            ensure_root(origin)?;
+            <Attesters<T>>::remove(&attester);
+            Self::deposit_event(RawEvent::AttesterRemoved(attester));
+            Ok(())
+        }
+
+        // Attest a claim about an account. Use attest_human() for Claim::UniqueHuman.
+        fn attest(origin, subject: T::AccountId, claim: Claim) -> Result {
+            let attester = ensure_signed(origin)?;
+            ensure!(claim != Claim::UniqueHuman, "use attest_human for unique humans");
+            ensure!(claim.is_well_formed(), "claim value is too long");
+            Self::issue(attester, subject, claim)
+        }
+
+        // Attest that an account belongs to a unique human. `human_id` is a hash only the attester can
+        // derive from the person's real identity, it keeps the same person from being attested twice.
+        // Other attesters can vouch for the same binding, it holds until all of them revoked.
+        fn attest_human(origin, subject: T::AccountId, human_id: T::Hash) -> Result {
+            let attester = ensure_signed(origin)?;
+            match Self::human_of(&human_id) {
+                Some(ref account) if account == &subject => {},
+                Some(_) => return Err("this person already has an attested account"),
+                None => ensure!(Self::human_id(&subject).is_none(), "account is already attested as a unique human"),
+            }
+            Self::issue(attester, subject.clone(), Claim::UniqueHuman)?;
+            <HumanOf<T>>::insert(&human_id, subject.clone());
+            <HumanId<T>>::insert(&subject, human_id);
+            <HumanAttesters<T>>::mutate(&human_id, |count| *count += 1);
+            Ok(())
+        }
+
+        // People can declare their own attributes, an empty value clears the attribute.
+        fn declare_attribute(origin, attribute: Attribute, value: Vec<u8>) -> Result {
+            let person = ensure_signed(origin)?;
+            ensure!(value.len() <= MAX_ATTRIBUTE_LEN, "attribute value is too long");
+            if value.is_empty() {
+                <Declared<T>>::remove(&(person.clone(), attribute));
+            } else {
+                <Declared<T>>::insert(&(person.clone(), attribute), value);
+            }
+            Self::deposit_event(RawEvent::AttributeDeclared(person, attribute));
+            Ok(())
+        }
+
+        // Revoke a claim the caller attested before.
+        fn revoke(origin, subject: T::AccountId, claim: Claim) -> Result {
+            let attester = ensure_signed(origin)?;
+            let mut attestations = Self::attestations(&subject);
+            let pos = attestations.iter()
+                .position(|a| a.attester == attester && a.claim == claim)
+                .ok_or("no such attestation")?;
+            attestations.remove(pos);
+            <Attestations<T>>::insert(&subject, attestations);
+            if claim == Claim::UniqueHuman {
+                if let Some(human_id) = Self::human_id(&subject) {
+                    let remaining = Self::human_attesters(&human_id).saturating_sub(1);
+                    if remaining == 0 {
+                        <HumanAttesters<T>>::remove(&human_id);
+                        <HumanOf<T>>::remove(&human_id);
+                        <HumanId<T>>::remove(&subject);
+                    } else {
+                        <HumanAttesters<T>>::insert(&human_id, remaining);
+                    }
+                }
+            }
+            Self::deposit_event(RawEvent::Revoked(attester, subject));
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+
+    fn issue(attester: T::AccountId, subject: T::AccountId, claim: Claim) -> Result {
+        ensure!(Self::is_attester(&attester), "not an approved attester");
+        ensure!(attester != subject, "you can't attest yourself");
+        let attestations = Self::attestations(&subject);
+        ensure!(!attestations.iter().any(|a| a.attester == attester && a.claim == claim), "already attested");
+        ensure!(attestations.len() < MAX_ATTESTATIONS, "too many attestations");
+        let attestation = Attestation {
+            attester: attester.clone(),
+            claim: claim,
+            issued_at: <system::Module<T>>::block_number(),
+        };
+        <Attestations<T>>::mutate(&subject, |attestations| attestations.push(attestation));
+        Self::deposit_event(RawEvent::Attested(attester, subject));
+        Ok(())
+    }
+
+    // Whether an account holds an attestation for every required claim.
+    // Attestations of attesters which were removed since do not count.
+    pub fn meets(who: &T::AccountId, required: &[Claim]) -> bool {
+        let attestations = Self::attestations(who);
+        required.iter().all(|claim| attestations.iter()
+            .any(|a| a.claim.satisfies(claim) && Self::is_attester(&a.attester)))
+    }
+
+    // Whether a predicate leaves exactly one value on the stack and stays within MAX_PREDICATE_OPS.
+    pub fn is_well_formed(predicate: &[PredicateOp]) -> bool {
+        if predicate.len() > MAX_PREDICATE_OPS {
+            return false;
+        }
+        let mut depth: usize = 0;
+        for op in predicate {
+            depth = match op {
+                PredicateOp::Is(_, value) | PredicateOp::AttestedIs(_, value) => {
+                    if value.len() > MAX_ATTRIBUTE_LEN {
+                        return false;
+                    }
+                    depth + 1
+                },
+                PredicateOp::And | PredicateOp::Or if depth >= 2 => depth - 1,
+                PredicateOp::Not if depth >= 1 => depth,
+                _ => return false,
+            };
+        }
+        predicate.is_empty() || depth == 1
+    }
+
+    // Evaluate an eligibility predicate for a person. The empty predicate admits everyone,
+    // a malformed one admits nobody.
+    pub fn evaluate(who: &T::AccountId, predicate: &[PredicateOp]) -> bool {
+        if predicate.is_empty() {
+            return true;
+        }
+        if !Self::is_well_formed(predicate) {
+            return false;
+        }
+        let mut stack: Vec<bool> = Vec::with_capacity(predicate.len());
+        for op in predicate {
+            let value = match op {
+                PredicateOp::Is(attribute, value) => {
+                    Self::declared(&(who.clone(), *attribute)).as_ref() == Some(value)
+                        || Self::is_attested(who, *attribute, value)
+                },
+                PredicateOp::AttestedIs(attribute, value) => Self::is_attested(who, *attribute, value),
+                PredicateOp::And => {
+                    let (b, a) = (stack.pop().unwrap_or(false), stack.pop().unwrap_or(false));
+                    a && b
+                },
+                PredicateOp::Or => {
+                    let (b, a) = (stack.pop().unwrap_or(false), stack.pop().unwrap_or(false));
+                    a || b
+                },
+                PredicateOp::Not => !stack.pop().unwrap_or(true),
+            };
+            stack.push(value);
+        }
+        stack.pop().unwrap_or(false)
+    }
+
+    fn is_attested(who: &T::AccountId, attribute: Attribute, value: &[u8]) -> bool {
+        Self::meets(who, &[Claim::Attribute(attribute, value.to_vec())])
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use runtime_io::with_externalities;
+    use primitives::{H256, Blake2Hasher};
+    use support::{impl_outer_origin, assert_ok, assert_noop};
+    use runtime_primitives::{
+        BuildStorage,
+        traits::{BlakeTwo256, IdentityLookup},
+        testing::{Digest, DigestItem, Header}
+    };
+
+    impl_outer_origin! {
+        pub enum Origin for Test {}
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Digest = Digest;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type Event = ();
+        type Log = DigestItem;
+    }
+    impl Trait for Test {
+        type Event = ();
+    }
+    type Attestation = Module<Test>;
+
+    const ATTESTER: u64 = 1;
+    const PERSON: u64 = 2;
+
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        system::GenesisConfig::<Test>::default().build_storage().unwrap().0.into()
+    }
+
+    #[test]
+    fn only_approved_attesters_can_attest() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(Attestation::attest(Origin::signed(ATTESTER), PERSON, Claim::AgeAtLeast(30)), "not an approved attester");
+            assert_ok!(Attestation::add_attester(Origin::ROOT, ATTESTER));
+            assert_ok!(Attestation::attest(Origin::signed(ATTESTER), PERSON, Claim::AgeAtLeast(30)));
+            assert!(Attestation::meets(&PERSON, &[Claim::AgeAtLeast(18)]));
+            assert!(!Attestation::meets(&PERSON, &[Claim::AgeAtLeast(40)]));
+
+            assert_ok!(Attestation::remove_attester(Origin::ROOT, ATTESTER));
+            assert!(!Attestation::meets(&PERSON, &[Claim::AgeAtLeast(18)]));
+        });
+    }
+
+    #[test]
+    fn predicates_combine_declared_and_attested_attributes() {
+        with_externalities(&mut new_test_ext(), || {
+            let eu_student = vec![
+                PredicateOp::AttestedIs(Attribute::Region, b"EU".to_vec()),
+                PredicateOp::Is(Attribute::Occupation, b"student".to_vec()),
+                PredicateOp::And,
+            ];
+            assert!(Attestation::is_well_formed(&eu_student));
+            assert!(!Attestation::is_well_formed(&[PredicateOp::And]));
+
+            assert_ok!(Attestation::declare_attribute(Origin::signed(PERSON), Attribute::Region, b"EU".to_vec()));
+            assert_ok!(Attestation::declare_attribute(Origin::signed(PERSON), Attribute::Occupation, b"student".to_vec()));
+            // a declared region is not enough where an attested one is required
+            assert!(!Attestation::evaluate(&PERSON, &eu_student));
+
+            assert_ok!(Attestation::add_attester(Origin::ROOT, ATTESTER));
+            assert_ok!(Attestation::attest(Origin::signed(ATTESTER), PERSON, Claim::Attribute(Attribute::Region, b"EU".to_vec())));
+            assert!(Attestation::evaluate(&PERSON, &eu_student));
+        });
+    }
+
+    #[test]
+    fn a_human_is_attested_only_once() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Attestation::add_attester(Origin::ROOT, ATTESTER));
+            let human_id = H256::from([7u8; 32]);
+            assert_ok!(Attestation::attest_human(Origin::signed(ATTESTER), PERSON, human_id));
+            assert_noop!(
+                Attestation::attest_human(Origin::signed(ATTESTER), 3, human_id),
+                "this person already has an attested account"
+            );
+            assert_ok!(Attestation::revoke(Origin::signed(ATTESTER), PERSON, Claim::UniqueHuman));
+            assert_ok!(Attestation::attest_human(Origin::signed(ATTESTER), 3, human_id));
+        });
+    }
+
+    #[test]
+    fn a_human_binding_lasts_until_every_attester_revoked() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Attestation::add_attester(Origin::ROOT, ATTESTER));
+            assert_ok!(Attestation::add_attester(Origin::ROOT, 4));
+            let human_id = H256::from([7u8; 32]);
+            assert_ok!(Attestation::attest_human(Origin::signed(ATTESTER), PERSON, human_id));
+            assert_noop!(Attestation::attest_human(Origin::signed(ATTESTER), PERSON, human_id), "already attested");
+            assert_ok!(Attestation::attest_human(Origin::signed(4), PERSON, human_id));
+
+            assert_ok!(Attestation::revoke(Origin::signed(ATTESTER), PERSON, Claim::UniqueHuman));
+            assert_eq!(Attestation::human_of(&human_id), Some(PERSON));
+            assert_noop!(
+                Attestation::attest_human(Origin::signed(ATTESTER), 3, human_id),
+                "this person already has an attested account"
+            );
+            assert_ok!(Attestation::revoke(Origin::signed(4), PERSON, Claim::UniqueHuman));
+            assert_eq!(Attestation::human_of(&human_id), None);
+            assert_eq!(Attestation::human_id(&PERSON), None);
+        });
+    }
+
+    #[test]
+    fn claims_are_bounded() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Attestation::add_attester(Origin::ROOT, ATTESTER));
+            assert_noop!(Attestation::attest(Origin::signed(ATTESTER), PERSON, Claim::Country(b"ATLANTIS".to_vec())), "claim value is too long");
+            for age in 0..MAX_ATTESTATIONS as u8 {
+                assert_ok!(Attestation::attest(Origin::signed(ATTESTER), PERSON, Claim::AgeAtLeast(age)));
+            }
+            assert_noop!(Attestation::attest(Origin::signed(ATTESTER), PERSON, Claim::AgeAtLeast(0)), "already attested");
+            assert_noop!(Attestation::attest(Origin::signed(ATTESTER), PERSON, Claim::Country(b"FR".to_vec())), "too many attestations");
+        });
+    }
+}
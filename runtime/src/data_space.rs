@@ -1,28 +1,240 @@
 use rstd::vec::Vec;
-use parity_codec::{Decode, Encode};
+use parity_codec::{Codec, Decode, Encode};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "std")]
+use runtime_io::with_storage;
 use support::{
-    decl_event, decl_module, decl_storage, dispatch::Result, ensure, StorageMap, StorageValue,traits::{Currency,LockIdentifier,LockableCurrency,WithdrawReasons}};
-use runtime_primitives::traits::{Bounded, One};
-use system::ensure_signed;
+    decl_event, decl_module, decl_storage, dispatch::Result, ensure, Parameter, StorageMap, StorageValue,traits::{Currency,ExistenceRequirement,LockIdentifier,LockableCurrency,ReservableCurrency,WithdrawReason,WithdrawReasons}};
+use runtime_primitives::traits::{As, Bounded, Hash, One, Saturating, Verify, Zero};
+use runtime_primitives::transaction_validity::TransactionValidity;
+use system::{ensure_inherent, ensure_root, ensure_signed};
+use crate::attestation::{self, Claim, PredicateOp, MAX_CLAIMS};
 
-pub trait Trait: system::Trait+balances::Trait {
+pub trait Trait: system::Trait+balances::Trait+attestation::Trait {
     type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
     type Currency: LockableCurrency<Self::AccountId, Moment=Self::BlockNumber>;
+    // Who signs the validation and aggregate reports computed off-chain.
+    type Reporters: Reporters;
+    // What companies sign delivery receipts with.
+    type Signature: Parameter + Verify<Signer = Self::AccountId>;
+    // Who approves companies, decides referred disputes and appeals, and changes the technical settings.
+    type Governance: Governance<Self::Origin>;
 }
 
+// The collective bodies deciding for the marketplace. Their decisions come as the origins of the motions they
+// passed, the runtime knows how to check them (e.g. council and technical committee motions).
+pub trait Governance<Origin> {
+    // Ok for root, or a motion passed by a majority of the council.
+    fn ensure_majority(origin: Origin) -> Result;
+    // Ok for root, or a motion passed by a majority of the technical committee.
+    fn ensure_technical(origin: Origin) -> Result;
+}
+
+// The keys trusted to report what was computed off-chain, the runtime knows who holds them (e.g. its authorities).
+// Reports come as unsigned extrinsics carrying the reporter's signature, see validate_unsigned().
+pub trait Reporters {
+    type Id: Parameter;
+    type Signature: Parameter + Verify<Signer = Self::Id>;
+    fn is_reporter(who: &Self::Id) -> bool;
+}
+
+pub type ReporterId<T> = <<T as Trait>::Reporters as Reporters>::Id;
+pub type ReporterSignature<T> = <<T as Trait>::Reporters as Reporters>::Signature;
+
 pub type BalanceOf<T> = <<T as Trait>::Currency as Currency<<T as system::Trait>::AccountId>>::Balance;
 
 const COMPANY_LOCK: LockIdentifier = *b"cpn_lock";
 const PERSON_LOCK: LockIdentifier = *b"psn_lock";
 
+// how many pending uploads a reporter validates per block
+const VALIDATIONS_PER_BLOCK: usize = 16;
+// the most uploads waiting for validation, the oldest one is dropped unvalidated to make room for a new one
+const MAX_PENDING_VALIDATIONS: u64 = 1024;
+// how long an unsigned report stays valid in the transaction pool
+const REPORT_LONGEVITY: u64 = 64;
+// the most sealed bids one auction can collect
+const MAX_BIDDERS: usize = 32;
+// how many uploads one bulk purchase can settle, bigger orders are bought over several calls
+const MAX_BULK_PURCHASE: usize = 128;
+// every upload a bulk purchase goes through is charged byte_fee for this many bytes, bought or not
+const BULK_ITEM_BYTES: usize = 32;
+// the most tags an order can have
+const MAX_TAGS: usize = 8;
+// the longest category or tag
+const MAX_LABEL_LEN: usize = 32;
+// the longest order_name or data_name
+const MAX_NAME_LEN: usize = 64;
+// the most scheduled items (disputes, appeals, auctions, periods, uploads, Data entries) on_finalize handles
+// in one block, the rest is carried over to the next block
+const MAX_FINALIZE_ITEMS: usize = 64;
+// how much evidence one dispute can collect
+const MAX_EVIDENCE: usize = 16;
+// the longest statement coming with a piece of evidence
+const MAX_STATEMENT_LEN: usize = 256;
+// the most orders one listing page can return
+const MAX_PAGE_SIZE: u32 = 100;
+// the most tiers a tiered pricing can have
+const MAX_PRICE_TIERS: usize = 8;
+// companies rate the data they confirm from 1 to MAX_RATING
+const MAX_RATING: u8 = 5;
+// the least share of the price, in percent, a rating table can pay for any rating
+const MIN_RATING_PERCENTAGE: u8 = 20;
+// the most fields an upload can disclose or an order can make mandatory
+const MAX_FIELDS: usize = 32;
+// the most histogram buckets an aggregate order can ask for
+const MAX_BUCKETS: usize = 16;
+// the most people who can answer one aggregate order
+const MAX_CONTRIBUTORS: u32 = 1024;
+// the most chunks one upload can be sent in
+const MAX_CHUNKS: u32 = 64;
+// every chunk reserves deposit_per_byte for this many bytes on top of its own, for its storage entries
+const CHUNK_DEPOSIT_BYTES: u64 = 64;
+// merkle trees hash leaves and inner nodes with different prefixes, so one can't be passed off as the other
+const MERKLE_LEAF: u8 = 0;
+const MERKLE_NODE: u8 = 1;
+// the most aggregate orders waiting for a result at once
+const MAX_PENDING_AGGREGATES: usize = 64;
+// an open aggregate order reserves deposit_per_byte for this many bytes, for its answers and its pending slot
+const AGGREGATE_DEPOSIT_BYTES: u64 = 256;
+// the length of an encrypted answer, see AggregateOrder
+pub const ANSWER_LEN: usize = 56;
+// prefix of the message a company signs to acknowledge a delivery
+const DELIVERY_CONTEXT: &[u8] = b"dataspace/delivery";
+// prefixes of the messages reporters sign
+const VALIDATION_CONTEXT: &[u8] = b"dataspace/validation";
+const AGGREGATE_CONTEXT: &[u8] = b"dataspace/aggregate";
+const AGGREGATION_KEY_CONTEXT: &[u8] = b"dataspace/aggregation-key";
+
+// Marketplace rules governance can change without a runtime upgrade.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+pub struct MarketParameters<BlockNumber> {
+    // blocks a person's account stays locked after a sale
+    pub person_lock_period: BlockNumber,
+    // blocks a company's account stays locked after it lost a dispute
+    pub company_lock_period: BlockNumber,
+    // validate_data() rejects data shorter than this
+    pub min_data_len: u32,
+    // validate_data() rejects data which just repeats the order content
+    pub reject_order_content: bool,
+    // only companies approved by root (a referendum) can publish orders
+    pub require_company_approval: bool,
+    // the longest answer window of an aggregate order
+    pub max_answer_period: BlockNumber,
+    // blocks the reporter has after the answer window to publish an aggregate before it expires
+    pub aggregate_report_period: BlockNumber,
+    // aggregate orders can't publish a result for fewer contributors than this
+    pub min_contributors: u32,
+    // the most privacy budget an aggregate order can spend, in thousandths
+    pub max_epsilon_millis: u32,
+}
+
+impl<BlockNumber: One + As<u64>> Default for MarketParameters<BlockNumber> {
+    fn default() -> Self {
+        MarketParameters {
+            person_lock_period: One::one(),
+            company_lock_period: One::one(),
+            min_data_len: 1,
+            reject_order_content: true,
+            require_company_approval: false,
+            max_answer_period: As::sa(100),
+            aggregate_report_period: As::sa(10),
+            min_contributors: 1,
+            max_epsilon_millis: 1000,
+        }
+    }
+}
+
+// Events only carry content hashes, the data itself is never broadcast.
 decl_event! {
     pub enum Event<T>
     where
-    <T as system::Trait>::AccountId
+    <T as system::Trait>::AccountId,
+    <T as system::Trait>::Hash,
+    <T as system::Trait>::BlockNumber,
+    Balance = <T as balances::Trait>::Balance
     {
-       Transfered(Vec<u8>),
-       Confirmed(AccountId,usize),
-       TippedOff(bool),
+       // company, order_id, unit_price
+       OrderPublished(AccountId, usize, Balance),
+       // person, company, order_id, content_hash
+       DataUploaded(AccountId, AccountId, usize, Hash),
+       // person, company, order_id, content_hash
+       DataUpdated(AccountId, AccountId, usize, Hash),
+       // person, company, order_id
+       DataErased(AccountId, AccountId, usize),
+       // person, company, order_id, upload_id
+       UploadStarted(AccountId, AccountId, usize, u64),
+       // person, upload_id
+       UploadAborted(AccountId, u64),
+       // person, company, order_id, number of disclosed fields
+       FieldsDisclosed(AccountId, AccountId, usize, u32),
+       // company, person, order_id, deal_id, amount, content_hash
+       DataPurchased(AccountId, AccountId, usize, u64, Balance, Hash),
+       // person, company, order_id, other order_id of the same company holding the same content
+       DataFlagged(AccountId, AccountId, usize, usize),
+       // person, company, order_id, passed
+       DataValidated(AccountId, AccountId, usize, bool),
+       // company, order_id
+       PricingChanged(AccountId, usize),
+       // deal_id, content_hash
+       DeliveryAcknowledged(u64, Hash),
+       // company, order_id, budget
+       AggregateOpened(AccountId, usize, Balance),
+       // person, company, order_id
+       AnswerSubmitted(AccountId, AccountId, usize),
+       // company, order_id, contributors, noisy mean
+       AggregatePublished(AccountId, usize, u32, u64),
+       // person, company, order_id, share
+       ShareClaimed(AccountId, AccountId, usize, Balance),
+       // company, order_id, contributors
+       AggregateCancelled(AccountId, usize, u32),
+       // company, order_id, no reporter published the aggregate in time
+       AggregateExpired(AccountId, usize),
+       // company, order_id, number of uploads bought
+       BulkPurchased(AccountId, usize, u32),
+       // deal_id, rating, amount paid to the person
+       DealConfirmed(u64, u8, Balance),
+       // person, company, order_id, reserve price
+       AuctionStarted(AccountId, AccountId, usize, Balance),
+       // person, company, order_id, bidder
+       BidCommitted(AccountId, AccountId, usize, AccountId),
+       // person, company, order_id, bidder, amount
+       BidRevealed(AccountId, AccountId, usize, AccountId, Balance),
+       // person, company, order_id, winner, winning bid
+       AuctionWon(AccountId, AccountId, usize, AccountId, Balance),
+       // person, company, order_id
+       AuctionFailed(AccountId, AccountId, usize),
+       // company, order_id, price per period, period
+       SubscriptionOffered(AccountId, usize, Balance, BlockNumber),
+       // person, company, order_id
+       Subscribed(AccountId, AccountId, usize),
+       // company, person, order_id, amount
+       SubscriptionCharged(AccountId, AccountId, usize, Balance),
+       // person, company, order_id, missed periods so far
+       PeriodMissed(AccountId, AccountId, usize, u32),
+       // person, company, order_id
+       SubscriptionCancelled(AccountId, AccountId, usize),
+       // deal_id
+       DisputeOpened(u64),
+       // deal_id, the dispute has evidence and goes to the council
+       DisputeReferred(u64),
+       // deal_id, winner
+       DisputeResolved(u64, AccountId),
+       // deal_id, reason
+       CompanyDisputed(u64, DisputeReason),
+       // company, bond after the change
+       BondChanged(AccountId, Balance),
+       // the market parameters or one of the limits were changed by root or the technical committee
+       ParametersChanged,
+       // company, approved
+       CompanyApproval(AccountId, bool),
+       // who, deal_id, evidence_hash
+       EvidenceSubmitted(AccountId, u64, Hash),
+       // appellant, deal_id
+       AppealFiled(AccountId, u64),
+       // deal_id, overturned
+       AppealDecided(u64, bool),
     }
 }
 
@@ -35,17 +247,352 @@ pub struct OrderForm<Balance> {
     order_name: Vec<u8>,
     content: Vec<u8>,
     unit_price: Balance,
+    // attested claims a person needs to hold to upload data for this order
+    requirements: Vec<Claim>,
+    // predicate over person attributes deciding who can upload data for this order
+    eligibility: Vec<PredicateOp>,
+    // what kind of data the order is for, e.g. "food", "health" or "mobility"
+    category: Vec<u8>,
+    tags: Vec<Vec<u8>>,
+}
+
+// How the price of an upload is computed when a company buys it, starting from the order's unit_price.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum Pricing<Balance, BlockNumber> {
+    // always unit_price
+    Flat,
+    // unit_price minus step for every upload submitted to the order before this one, never below floor
+    LinearDecrease { step: Balance, floor: Balance },
+    // (submissions, price): the price of the first tier whose submission count was not reached
+    // when the upload was submitted, the last tier's price for later uploads
+    Tiers(Vec<(u32, Balance)>),
+    // unit_price minus step for every interval blocks since the pricing was set, never below floor
+    Decay { interval: BlockNumber, step: Balance, floor: Balance },
+}
+
+impl<Balance, BlockNumber> Default for Pricing<Balance, BlockNumber> {
+    fn default() -> Self {
+        Pricing::Flat
+    }
 }
 
 // People upload data to make a deal with company.
 // Datametadata is some metadata of what they upload.
 #[derive(Encode, Decode, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "std", derive(Debug))]
-pub struct DataMetadata<AccountId>{
+pub struct DataMetadata<AccountId, Hash>{
+    data_name: Vec<u8>,
+    to_company: AccountId,
+    order_id: usize,
+    hash_key: u64,
+    // the hash the content stored under hash_key was committed to, see DataRecord
+    content_hash: Hash,
+}
+
+// Every Data entry keeps track of who stored it and how much deposit is reserved for it.
+// The deposit is returned when the entry is erased, either by its owner or once it expires.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct DataRecord<AccountId, Balance, Hash, BlockNumber> {
+    owner: AccountId,
+    deposit: Balance,
+    // the hash of the content, or the merkle root of its chunks when it was sent with begin_upload()
+    content_hash: Hash,
+    // length of the content in bytes
+    size: u32,
+    // content sent with begin_upload() stays in Chunks, as (upload_id, chunk_count)
+    chunks: Option<(u64, u32)>,
+    // whether any company has bought this data yet
+    purchased: bool,
+    // the block at which the entry is garbage-collected
+    expires_at: BlockNumber,
+}
+
+// An upload too large for one extrinsic, sent chunk by chunk and committed to a merkle root of the chunk hashes.
+// Chunks can be sent in any order and sent again, the upload stays open while chunks keep coming.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ChunkedUpload<AccountId, Balance, Hash, BlockNumber> {
+    owner: AccountId,
     data_name: Vec<u8>,
     to_company: AccountId,
     order_id: usize,
+    total_size: u32,
+    chunk_count: u32,
+    root_hash: Hash,
+    // chunks received so far, their hashes are in ChunkHashes
+    chunks_received: u32,
+    // bytes received so far
+    received: u32,
+    // reserved for the chunks received so far
+    deposit: Balance,
+    expires_at: BlockNumber,
+}
+
+// The result of the off-chain checks run against an upload, companies can look at it before they pay.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct ValidationReport<Hash, BlockNumber> {
+    // the content which was checked, a report is void once the data is updated
+    content_hash: Hash,
+    // the data is not empty and fits max_data_size, or max_upload_size for chunked uploads
+    size_ok: bool,
+    // the data follows the rules of validate_data() for its order
+    schema_ok: bool,
+    // another upload to the same order has the same content
+    duplicate: bool,
+    reported_at: BlockNumber,
+}
+
+#[derive(Encode, Decode, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum DealStatus {
+    // the price is held in escrow until the company confirms the data
+    Paid,
+    // the price was released to the person
+    Confirmed,
+    // a dispute about the deal waits for its verdict
+    Disputed,
+    // the price was returned to the company because the data was illegal
+    Refunded,
+}
+
+impl Default for DealStatus {
+    fn default() -> Self {
+        DealStatus::Paid
+    }
+}
+
+// A purchase of one upload. The price stays reserved on the buyer's account (the escrow)
+// until the deal is confirmed or a tip-off decides who gets it.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Deal<AccountId, Balance, Hash, BlockNumber> {
+    buyer: AccountId,
+    person: AccountId,
+    // the company whose order the data was uploaded for, the buyer unless it won an auction
+    to_company: AccountId,
+    order_id: usize,
+    price: Balance,
+    // the content the buyer paid for, and the Data entry holding it
+    content_hash: Hash,
     hash_key: u64,
+    // index of that content in the Versions of the upload
+    version: u32,
+    status: DealStatus,
+    purchased_at: BlockNumber,
+    // the content hash the buyer acknowledged receiving, and when
+    receipt: Option<(Hash, BlockNumber)>,
+    // the company's rating of the data, 0 until the deal is confirmed
+    rating: u8,
+    // the order's rating table when the data was bought, empty if the deal pays in full
+    rating_table: Vec<u8>,
+}
+
+// One content an upload had, versions are only ever appended.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct DataVersion<Hash, BlockNumber> {
+    content_hash: Hash,
+    uploaded_at: BlockNumber,
+}
+
+// Why a person disputes a purchase.
+#[derive(Encode, Decode, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum DisputeReason {
+    // the company keeps the price in escrow without confirming the data
+    NeverConfirmed,
+    // the company abuses the order, e.g. rejecting or misusing data it got
+    Abusive,
+}
+
+// A hash of evidence kept off-chain and a short statement about it.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Evidence<AccountId, Hash, BlockNumber> {
+    from: AccountId,
+    evidence_hash: Hash,
+    statement: Vec<u8>,
+    submitted_at: BlockNumber,
+}
+
+// The timeline of a dispute about a deal: both sides submit evidence until evidence_end. A dispute
+// without evidence is then judged by the automatic check, one with evidence goes to the council first.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Dispute<AccountId, Hash, BlockNumber> {
+    opened_by: AccountId,
+    // why the person disputes the deal, None for a company's tip-off
+    reason: Option<DisputeReason>,
+    opened_at: BlockNumber,
+    evidence_end: BlockNumber,
+    evidence: Vec<Evidence<AccountId, Hash, BlockNumber>>,
+    // until when the council can decide, None unless the dispute was referred to it
+    council_end: Option<BlockNumber>,
+    // the winner and when the verdict was given
+    verdict: Option<(AccountId, BlockNumber)>,
+}
+
+// The losing side of a dispute asking the council to overturn the verdict.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Appeal<AccountId, Balance, BlockNumber> {
+    appellant: AccountId,
+    bond: Balance,
+    // until when the council can overturn the verdict
+    voting_end: BlockNumber,
+    // whether the verdict was overturned, None until the council decides or voting_end
+    overturned: Option<bool>,
+}
+
+// A sealed-bid auction for exclusive access to an upload.
+// Companies commit to hash((amount, salt)) until commit_end and reveal until reveal_end,
+// the highest revealed bid becomes the only deal on the upload.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Auction<AccountId, Balance, BlockNumber> {
+    // the lowest acceptable bid
+    reserve_price: Balance,
+    // what every bidder reserves with its commitment, the larger of reserve_price and min_bid_deposit
+    bond: Balance,
+    commit_end: BlockNumber,
+    reveal_end: BlockNumber,
+    // the highest revealed bid so far, its amount is reserved on the bidder's account
+    best_bid: Option<(AccountId, Balance)>,
+}
+
+// Terms of a recurring order: subscribers submit fresh data every period and get paid price for it.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct SubscriptionTerms<Balance, BlockNumber> {
+    period: BlockNumber,
+    price: Balance,
+}
+
+// An order for an aggregate instead of raw data. During the answer window people submit a number in
+// [0, max_value] encrypted to the aggregation key of a reporter the company picked, the chain never sees
+// an answer in the clear. Once the window closes that reporter decrypts the answers off-chain and
+// publishes the mean and the histogram with Laplace noise calibrated to epsilon. The budget is shared
+// equally between everyone whose answer was valid, each contributor claims their share.
+//
+// An answer is ANSWER_LEN bytes: an ephemeral x25519 public key E, the value as 8 little-endian bytes
+// xor the first 8 bytes of k = blake2_256((x25519(e, encryption_key), company, order_id, person).encode()),
+// and the first 16 bytes of blake2_256(k ++ the 8 encrypted bytes).
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AggregateOrder<Balance, BlockNumber> {
+    // the aggregation key of the reporter who computes the aggregate
+    encryption_key: [u8; 32],
+    max_value: u64,
+    // ascending upper bounds of the histogram buckets, the last bucket takes everything above
+    buckets: Vec<u64>,
+    // the privacy budget spent on each of the mean and the histogram, in thousandths
+    epsilon_millis: u32,
+    // no aggregate is published for fewer contributors
+    min_contributors: u32,
+    budget: Balance,
+    answer_end: BlockNumber,
+    // the order expires if no aggregate is published by then
+    report_end: BlockNumber,
+    // reserved while the order waits for its result
+    deposit: Balance,
+}
+
+// What the company gets instead of the answers.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AggregateResult<Balance> {
+    contributors: u32,
+    mean: u64,
+    histogram: Vec<u64>,
+    // what every valid answer is paid
+    share: Balance,
+    // the indices of the answers which are not paid, ascending
+    rejected: Vec<u32>,
+}
+
+// A person's subscription to a recurring order.
+#[derive(Encode, Decode, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Subscription<BlockNumber> {
+    // the block at which the current period ends
+    period_end: BlockNumber,
+    // whether fresh data was submitted during the current period
+    submitted: bool,
+    paid_periods: u32,
+    missed_periods: u32,
+}
+
+// What the off-chain checks found out about the current content of an upload, a reporter signs it.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct UploadCheck<AccountId, Hash> {
+    pub person: AccountId,
+    pub company: AccountId,
+    pub order_id: usize,
+    pub content_hash: Hash,
+    pub size_ok: bool,
+    pub schema_ok: bool,
+    pub duplicate: bool,
+}
+
+impl<AccountId: Encode, Hash: Encode> UploadCheck<AccountId, Hash> {
+    // the message a reporter signs
+    pub fn payload(&self) -> Vec<u8> {
+        let mut payload = VALIDATION_CONTEXT.to_vec();
+        payload.extend(self.encode());
+        payload
+    }
+}
+
+// The noisy aggregate of an aggregate order, a reporter signs it.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AggregateReport<AccountId> {
+    pub company: AccountId,
+    pub order_id: usize,
+    pub mean: u64,
+    pub histogram: Vec<u64>,
+    // the indices of the answers which did not decrypt to a value in range, ascending
+    pub rejected: Vec<u32>,
+}
+
+impl<AccountId: Encode> AggregateReport<AccountId> {
+    // the message a reporter signs
+    pub fn payload(&self) -> Vec<u8> {
+        let mut payload = AGGREGATE_CONTEXT.to_vec();
+        payload.extend(self.encode());
+        payload
+    }
+}
+
+// What the reporter holding an aggregation key needs to compute an aggregate.
+#[derive(Encode, Decode, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct AggregateInput<AccountId> {
+    pub company: AccountId,
+    pub order_id: usize,
+    pub encryption_key: [u8; 32],
+    pub max_value: u64,
+    pub buckets: Vec<u64>,
+    pub epsilon_millis: u32,
+    // (person, encrypted answer), the position is the index of the answer
+    pub answers: Vec<(AccountId, Vec<u8>)>,
+}
+
+// the message a reporter signs to register its aggregation key
+pub fn aggregation_key_payload(key: &[u8; 32]) -> Vec<u8> {
+    let mut payload = AGGREGATION_KEY_CONTEXT.to_vec();
+    payload.extend_from_slice(key);
+    payload
+}
+
+impl<Hash, BlockNumber> ValidationReport<Hash, BlockNumber> {
+    pub fn passed(&self) -> bool {
+        self.size_ok && self.schema_ok && !self.duplicate
+    }
 }
 
 
@@ -53,20 +600,369 @@ decl_storage! {
     trait Store for Module<T: Trait> as DataStore {
         // store the order forms of every company
         pub Company get(get_order): map T::AccountId => Vec<OrderForm<T::Balance>>;
+        // pricing of an order and the block it was set at, orders without one are Flat
+        OrderPricing get(order_pricing): map (T::AccountId, usize) => Option<(Pricing<T::Balance, T::BlockNumber>, T::BlockNumber)>;
+        // how many uploads were ever submitted to an order, keyed by (company, order_id)
+        Submissions get(submissions): map (T::AccountId, usize) => u32;
+        // how many uploads were submitted to the order before an upload, keyed by (person, company, order_id)
+        SubmissionIndex get(submission_index): map (T::AccountId, T::AccountId, usize) => u32;
+        // percentage of the price paid for each rating from 1 to MAX_RATING, orders without one pay in full
+        RatingTable get(rating_table): map (T::AccountId, usize) => Option<Vec<u8>>;
+        // (number of ratings, sum of ratings) a person received for confirmed data
+        Reputation get(reputation): map T::AccountId => (u32, u32);
+        // aggregate orders keyed by (company, order_id)
+        AggregateOrders get(aggregate_order): map (T::AccountId, usize) => Option<AggregateOrder<T::Balance, T::BlockNumber>>;
+        // encrypted answers keyed by (company, order_id, index), as (person, answer)
+        Answers get(answer): map (T::AccountId, usize, u32) => Option<(T::AccountId, Vec<u8>)>;
+        // how many answers an aggregate order received
+        AnswerCount get(answer_count): map (T::AccountId, usize) => u32;
+        // the index of a person's answer keyed by (company, order_id, person), until the share is claimed
+        AnswerIndex get(answer_index): map (T::AccountId, usize, T::AccountId) => Option<u32>;
+        // aggregate orders waiting for a reporter to publish their result, at most MAX_PENDING_AGGREGATES
+        PendingAggregates get(pending_aggregates): Vec<(T::AccountId, usize)>;
+        // aggregate orders which expire at a block unless their aggregate was published
+        AggregateEnds get(aggregate_ends): map T::BlockNumber => Vec<(T::AccountId, usize)>;
+        // the noisy aggregate of an order
+        AggregateResults get(aggregate_result): map (T::AccountId, usize) => Option<AggregateResult<T::Balance>>;
+        // the reporter holding each registered aggregation key
+        AggregationKeys get(aggregation_key_owner): map [u8; 32] => Option<ReporterId<T>>;
+        // every published order (company, order_id), keyed by its position in publishing order
+        OrderIndex get(order_at): map u32 => Option<(T::AccountId, usize)>;
+        OrderCount get(order_count): u32;
+        // the orders published in a category, keyed by (category, position in the category)
+        CategoryOrders get(category_order_at): map (Vec<u8>, u32) => Option<(T::AccountId, usize)>;
+        CategoryCount get(category_count): map Vec<u8> => u32;
+        // the orders carrying a tag, keyed by (tag, position under the tag)
+        TagOrders get(tag_order_at): map (Vec<u8>, u32) => Option<(T::AccountId, usize)>;
+        TagCount get(tag_count): map Vec<u8> => u32;
         // store the metadata of every people's data
-        People get(get_data): map T::AccountId => Vec<DataMetadata<T::AccountId>>;
+        People get(get_data): map T::AccountId => Vec<DataMetadata<T::AccountId, T::Hash>>;
         // where people data is actually stored
         Data get(get_content): map u64 => Vec<u8>;
         // use Nonce to assign hash_key to people's data
         Nonce get(get_n): u64;
 
+        // companies root approved to publish orders when the parameters require it
+        ApprovedCompanies get(is_approved_company): map T::AccountId => bool;
+        // marketplace rules, see MarketParameters
+        pub Parameters get(parameters) config(): MarketParameters<T::BlockNumber>;
+        // the largest data_content (in bytes) a person can upload or update in one call
+        pub MaxDataSize get(max_data_size) config(): u32;
+        // the largest order form content (in bytes) a company can publish
+        pub MaxOrderContentSize get(max_order_content_size) config(): u32;
+        // fee charged for every byte a call stores on chain
+        pub ByteFee get(byte_fee) config(): T::Balance;
+
+        // deposit reserved from the uploader for every byte kept in Data
+        pub DepositPerByte get(deposit_per_byte) config(): T::Balance;
+        // blocks after which a never-purchased upload is treated as abandoned and erased
+        pub AbandonPeriod get(abandon_period) config(): T::BlockNumber;
+        // blocks a purchased upload is kept after its last purchase
+        pub RetentionPeriod get(retention_period) config(): T::BlockNumber;
+        // blocks a buyer has to confirm a purchase before the person can dispute it as never confirmed
+        pub DeliveryPeriod get(delivery_period) config(): T::BlockNumber;
+        // owner, deposit and lifetime of every Data entry
+        DataInfo get(get_data_info): map u64 => Option<DataRecord<T::AccountId, T::Balance, T::Hash, T::BlockNumber>>;
+        // Data entries which are due to be collected at a block
+        Expiring get(expiring_at): map T::BlockNumber => Vec<u64>;
+
+        // field indices every upload to an order has to disclose, keyed by (company, order_id)
+        MandatoryFields get(mandatory_fields): map (T::AccountId, usize) => Vec<u32>;
+        // merkle root of all the fields of an upload and the indices disclosed, keyed by (person, company, order_id)
+        FieldCommitments get(field_commitment): map (T::AccountId, T::AccountId, usize) => Option<(T::Hash, Vec<u32>)>;
+
+        // the largest data (in bytes) a person can upload in chunks
+        pub MaxUploadSize get(max_upload_size) config(): u32;
+        // blocks an unfinished chunked upload is kept after its last chunk
+        pub UploadTimeout get(upload_timeout) config(): T::BlockNumber;
+        // unfinished chunked uploads
+        ChunkedUploads get(chunked_upload): map u64 => Option<ChunkedUpload<T::AccountId, T::Balance, T::Hash, T::BlockNumber>>;
+        // received chunks keyed by (upload_id, index)
+        Chunks get(get_chunk): map (u64, u32) => Vec<u8>;
+        // the merkle leaf (see chunk_leaf()) of every received chunk keyed by (upload_id, index)
+        ChunkHashes get(chunk_hash): map (u64, u32) => Option<T::Hash>;
+        // chunked uploads which time out at a block
+        UploadTimeouts get(upload_timeouts): map T::BlockNumber => Vec<u64>;
+        UploadNonce get(upload_nonce): u64;
+
+        // people who uploaded data to an order keyed by (company, order_id, position), erased uploads leave their position empty
+        UploaderAt get(uploader_at): map (T::AccountId, usize, u32) => Option<T::AccountId>;
+        UploaderCount get(uploader_count): map (T::AccountId, usize) => u32;
+        // the UploaderAt position of an upload (person, company, order_id)
+        UploaderPosition get(uploader_position): map (T::AccountId, T::AccountId, usize) => Option<u32>;
+        // how far buy_all() got through the UploaderAt positions of an order
+        BuyCursor get(buy_cursor): map (T::AccountId, usize) => u32;
+        // uploads (person, company, order_id) waiting for a reporter to validate them, with their ValidationQueue position
+        PendingValidation get(pending_position): map (T::AccountId, T::AccountId, usize) => Option<u64>;
+        // pending uploads in the order they are validated, keyed by position
+        ValidationQueue get(validation_queue): map u64 => Option<(T::AccountId, T::AccountId, usize)>;
+        // (first, next free) position of ValidationQueue, it never holds more than MAX_PENDING_VALIDATIONS positions
+        ValidationQueueBounds get(validation_queue_bounds): (u64, u64);
+        // latest validation report of every upload, keyed by (person, company, order_id)
+        Reports get(get_report): map (T::AccountId, T::AccountId, usize) => Option<ValidationReport<T::Hash, T::BlockNumber>>;
+
+        // who uploaded a content to an order, keyed by (company, order_id, content_hash)
+        OrderContent get(order_content): map (T::AccountId, usize, T::Hash) => Option<T::AccountId>;
+        // the orders of a company which received a content, keyed by (company, content_hash)
+        CompanyContent get(company_content): map (T::AccountId, T::Hash) => Vec<usize>;
+        // orders whose uploads are checked against the other orders of the same company
+        CrossOrderCheck get(cross_order_check): map (T::AccountId, usize) => bool;
+        // uploads to an order whose content also went to another order of the company, as (person, other order_id)
+        Flags get(flagged_uploads): map (T::AccountId, usize) => Vec<(T::AccountId, usize)>;
+
+        // every content an upload had, oldest first, keyed by (person, company, order_id)
+        Versions get(versions): map (T::AccountId, T::AccountId, usize) => Vec<DataVersion<T::Hash, T::BlockNumber>>;
+        // what buyers of an order pay for a newer version of data they bought, the current price if unset
+        UpgradePrice get(upgrade_price): map (T::AccountId, usize) => Option<T::Balance>;
+        // bond companies keep reserved, disputes they lose are paid from it
+        CompanyBond get(company_bond): map T::AccountId => T::Balance;
+        // how many deals of a company are still held in escrow
+        PaidDeals get(paid_deals): map T::AccountId => u32;
+        // the end of the last appeal window of a verdict on a company's deals
+        AppealableUntil get(appealable_until): map T::AccountId => T::BlockNumber;
+        // how many appeals about a company's deals are waiting for the council
+        OpenAppeals get(open_appeals): map T::AccountId => u32;
+        // disputes keyed by deal id, kept after their verdict
+        Disputes get(get_dispute): map u64 => Option<Dispute<T::AccountId, T::Hash, T::BlockNumber>>;
+        // disputes whose evidence window ends at a block
+        DisputeEnds get(dispute_ends): map T::BlockNumber => Vec<u64>;
+        // blocks both sides of a dispute have to submit evidence
+        pub EvidencePeriod get(evidence_period) config(): T::BlockNumber;
+        // what a company paid from its bond for each dispute it lost, so an appeal can give it back
+        PenaltiesPaid get(penalty_paid): map u64 => T::Balance;
+        // appeals keyed by the deal id of the dispute, one per dispute
+        Appeals get(get_appeal): map u64 => Option<Appeal<T::AccountId, T::Balance, T::BlockNumber>>;
+        // appeals whose council vote ends at a block
+        AppealEnds get(appeal_ends): map T::BlockNumber => Vec<u64>;
+        // blocks after a verdict the losing side can appeal it
+        pub AppealPeriod get(appeal_period) config(): T::BlockNumber;
+        // blocks the council votes on an appeal
+        pub AppealVotingPeriod get(appeal_voting_period) config(): T::BlockNumber;
+        // blocks the council has to decide a dispute referred to it
+        pub DisputeVotingPeriod get(dispute_voting_period) config(): T::BlockNumber;
+        // bond of an appeal, kept higher than dispute_penalty so appealing is not the default
+        pub AppealBond get(appeal_bond) config(): T::Balance;
+        // the bond a company needs to buy data
+        pub MinCompanyBond get(min_company_bond) config(): T::Balance;
+        // what a company loses from its bond to the person for every dispute it loses
+        pub DisputePenalty get(dispute_penalty) config(): T::Balance;
+        // purchases keyed by deal id
+        Deals get(get_deal): map u64 => Option<Deal<T::AccountId, T::Balance, T::Hash, T::BlockNumber>>;
+        // the latest deal of a buyer for an upload, keyed by (buyer, person, company, order_id)
+        DealIds get(deal_id): map (T::AccountId, T::AccountId, T::AccountId, usize) => Option<u64>;
+        // use DealCount to assign ids to deals
+        DealCount get(deal_count): u64;
+        // the smallest bond a sealed bid reserves, so bids can't be committed for free
+        pub MinBidDeposit get(min_bid_deposit) config(): T::Balance;
+        // running auctions keyed by the upload (person, company, order_id)
+        Auctions get(get_auction): map (T::AccountId, T::AccountId, usize) => Option<Auction<T::AccountId, T::Balance, T::BlockNumber>>;
+        // sealed bids keyed by (person, company, order_id, bidder)
+        Commitments get(get_commitment): map (T::AccountId, T::AccountId, usize, T::AccountId) => Option<T::Hash>;
+        // bidders which committed to an auction and did not reveal yet
+        Bidders get(bidders): map (T::AccountId, T::AccountId, usize) => Vec<T::AccountId>;
+        // auctions whose reveal window ends at a block
+        AuctionEnds get(auction_ends): map T::BlockNumber => Vec<(T::AccountId, T::AccountId, usize)>;
+        // the winner of the auction of an upload, nobody else can buy it
+        ExclusiveTo get(exclusive_to): map (T::AccountId, T::AccountId, usize) => Option<T::AccountId>;
+
+        // recurring orders keyed by (company, order_id)
+        SubscriptionOffers get(subscription_terms): map (T::AccountId, usize) => Option<SubscriptionTerms<T::Balance, T::BlockNumber>>;
+        // what is left of the amount a company reserved to pay the subscribers of an order
+        Allowances get(allowance): map (T::AccountId, usize) => T::Balance;
+        // subscriptions keyed by (person, company, order_id)
+        Subscriptions get(get_subscription): map (T::AccountId, T::AccountId, usize) => Option<Subscription<T::BlockNumber>>;
+        // subscriptions whose period ends at a block
+        PeriodEnds get(period_ends): map T::BlockNumber => Vec<(T::AccountId, T::AccountId, usize)>;
+    }
+    // A marketplace to start dev and test chains with. Nobody is charged for it and seeded uploads carry no deposit.
+    add_extra_genesis {
+        // companies registered without any order yet
+        config(companies): Vec<T::AccountId>;
+        // (company, order_name, content, unit_price, category), order ids are given in this order per company
+        config(orders): Vec<(T::AccountId, Vec<u8>, Vec<u8>, T::Balance, Vec<u8>)>;
+        // (person, company, order_id, data_name, data_content)
+        config(uploads): Vec<(T::AccountId, T::AccountId, usize, Vec<u8>, Vec<u8>)>;
+        build(|storage: &mut runtime_primitives::StorageOverlay, _: &mut runtime_primitives::ChildrenStorageOverlay, config: &GenesisConfig<T>| {
+            with_storage(storage, || {
+                for company in &config.companies {
+                    if !<Company<T>>::exists(company) {
+                        <Company<T>>::insert(company, Vec::new());
+                    }
+                    <ApprovedCompanies<T>>::insert(company, true);
+                }
+                for (company, order_name, content, unit_price, category) in config.orders.iter().cloned() {
+                    assert!(order_name.len() <= MAX_NAME_LEN, "genesis order name is too long");
+                    <Module<T>>::insert_order(company, order_name, content, unit_price, Vec::new(), Vec::new(), category, Vec::new());
+                }
+                for (person, company, order_id, data_name, data_content) in config.uploads.iter().cloned() {
+                    assert!(<Module<T>>::get_orderform(&company, order_id).is_some(), "genesis upload to an unknown order");
+                    assert!(<Module<T>>::get_metadata(&person, &company, order_id).is_none(), "genesis uploads twice to an order");
+                    let content_hash = T::Hashing::hash(&data_content);
+                    let hash_key = <Module<T>>::put_on_chain(&person, data_content, content_hash, Zero::zero());
+                    <Module<T>>::index_upload(person, data_name, hash_key, content_hash, company, order_id);
+                }
+            });
+        });
     }
 }
 
+// This Substrate version has no dispatch weights, so the calls below carry no weight annotation.
+// What a call costs instead: every extrinsic pays transaction_base_fee plus transaction_byte_fee for its
+// encoded length (see the balances genesis config), which grows with the payload, and calls which store
+// data pay byte_fee for every stored byte on top (charge_for_bytes()). Calls going through several items are
+// bounded by the constants above, and on_finalize handles at most MAX_FINALIZE_ITEMS items per block.
 decl_module! {
     pub struct Module<T: Trait> for enum Call where origin: T::Origin {
         fn deposit_event<T>() = default;
 
+        // Settle what is due in this block: disputes, appeals, auctions, subscription periods, expired aggregate orders,
+        // timed-out uploads, and collect the Data entries whose retention period ends and refund their deposits.
+        // Items over MAX_FINALIZE_ITEMS wait for the next block, that's why due items are checked with <= n.
+        fn on_finalize(n: T::BlockNumber) {
+            let mut budget = MAX_FINALIZE_ITEMS;
+            for deal_id in Self::take_scheduled::<_, DisputeEnds<T>>(n, &mut budget) {
+                Self::resolve_dispute(deal_id);
+            }
+
+            // appeals the council did not overturn in time are upheld
+            for deal_id in Self::take_scheduled::<_, AppealEnds<T>>(n, &mut budget) {
+                Self::close_appeal(deal_id, false);
+            }
+
+            for upload in Self::take_scheduled::<_, AuctionEnds<T>>(n, &mut budget) {
+                Self::settle_auction(upload);
+            }
+
+            for subscription in Self::take_scheduled::<_, PeriodEnds<T>>(n, &mut budget) {
+                Self::close_period(subscription, n);
+            }
+
+            for key in Self::take_scheduled::<_, AggregateEnds<T>>(n, &mut budget) {
+                Self::expire_aggregate(key);
+            }
+
+            for upload_id in Self::take_scheduled::<_, UploadTimeouts<T>>(n, &mut budget) {
+                if let Some(upload) = Self::chunked_upload(upload_id) {
+                    // the upload may have been extended after it was put in this bucket
+                    if upload.expires_at <= n {
+                        Self::abort_upload(upload_id, upload);
+                    }
+                }
+            }
+
+            for hash_key in Self::take_scheduled::<_, Expiring<T>>(n, &mut budget) {
+                if let Some(record) = Self::get_data_info(hash_key) {
+                    // the entry may have been rescheduled after it was put in this bucket
+                    if record.expires_at <= n {
+                        match Self::get_data(&record.owner).into_iter().find(|m| m.hash_key == hash_key) {
+                            Some(metadata) => Self::erase_upload(&record.owner, &metadata),
+                            None => Self::remove_from_chain(hash_key),
+                        }
+                    }
+                }
+            }
+        }
+
+        // Reporters register the x25519 key people encrypt their aggregate answers to. The node derives
+        // it from the authority key and submits it as an unsigned extrinsic.
+        fn register_aggregation_key(origin, key: [u8; 32], reporter: ReporterId<T>, signature: ReporterSignature<T>) -> Result {
+            ensure_inherent(origin)?;
+            Self::check_reporter(&aggregation_key_payload(&key), &reporter, &signature)?;
+            ensure!(Self::aggregation_key_owner(&key).is_none(), "aggregation key is already registered");
+            <AggregationKeys<T>>::insert(&key, reporter);
+            Ok(())
+        }
+
+        // Record the noisy aggregate of an order, the contributors claim their share with claim_share().
+        // The aggregate is computed off-chain by the reporter holding the order's aggregation key (see
+        // aggregate_inputs()), whose node submits it as an unsigned extrinsic.
+        fn report_aggregate(origin, report: AggregateReport<T::AccountId>, reporter: ReporterId<T>, signature: ReporterSignature<T>) -> Result {
+            ensure_inherent(origin)?;
+            Self::check_reporter(&report.payload(), &reporter, &signature)?;
+            let AggregateReport { company, order_id, mean, histogram, rejected } = report;
+            let key = (company.clone(), order_id);
+            ensure!(Self::pending_aggregates().contains(&key), "aggregate is not waiting for a result");
+            let aggregate = Self::aggregate_order(&key).ok_or("no aggregate order")?;
+            ensure!(Self::aggregation_key_owner(&aggregate.encryption_key) == Some(reporter), "answers are not encrypted to this reporter");
+            ensure!(<system::Module<T>>::block_number() >= aggregate.answer_end, "answer window is not over");
+            ensure!(histogram.len() == aggregate.buckets.len() + 1, "histogram does not match the buckets");
+            let answers = Self::answer_count(&key);
+            ensure!(rejected.windows(2).all(|w| w[0] < w[1]), "rejected answers must be ascending");
+            ensure!(rejected.iter().all(|&index| index < answers), "no such answer");
+
+            <PendingAggregates<T>>::mutate(|pending| pending.retain(|k| k != &key));
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&company, aggregate.deposit);
+            let count = answers - rejected.len() as u32;
+            if count < aggregate.min_contributors || count == 0 {
+                // too few answers to hide anyone in the crowd
+                <balances::Module<T> as ReservableCurrency<_>>::unreserve(&company, aggregate.budget);
+                Self::deposit_event(RawEvent::AggregateCancelled(company, order_id, count));
+                return Ok(());
+            }
+
+            // the shares stay reserved on the company until they are claimed
+            let share = aggregate.budget / <T::Balance as As<u64>>::sa(count as u64);
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&company, aggregate.budget - share * <T::Balance as As<u64>>::sa(count as u64));
+            <AggregateResults<T>>::insert(&key, AggregateResult {
+                contributors: count,
+                mean: mean,
+                histogram: histogram,
+                share: share,
+                rejected: rejected,
+            });
+            Self::deposit_event(RawEvent::AggregatePublished(company, order_id, count, mean));
+            Ok(())
+        }
+
+        // People whose answer went into a published aggregate claim their share of the budget.
+        fn claim_share(origin, to_company: T::AccountId, order_id: usize) -> Result {
+            let person = ensure_signed(origin)?;
+            let result = Self::aggregate_result(&(to_company.clone(), order_id)).ok_or("no aggregate result")?;
+            let answer_key = (to_company.clone(), order_id, person.clone());
+            let index = Self::answer_index(&answer_key).ok_or("nothing to claim")?;
+            ensure!(result.rejected.binary_search(&index).is_err(), "your answer was rejected");
+
+            <balances::Module<T> as ReservableCurrency<_>>::repatriate_reserved(&to_company, &person, result.share)?;
+            <AnswerIndex<T>>::remove(&answer_key);
+            <Answers<T>>::remove(&(to_company.clone(), order_id, index));
+            Self::deposit_event(RawEvent::ShareClaimed(person, to_company, order_id, result.share));
+            Ok(())
+        }
+
+        // Record the validation report of an upload. The checks run off-chain (see pending_checks()) and are signed
+        // by a reporter, whose node submits them as an unsigned extrinsic.
+        // Only the first report for the current content of a pending upload is accepted.
+        fn report_validation(origin, check: UploadCheck<T::AccountId, T::Hash>, reporter: ReporterId<T>, signature: ReporterSignature<T>) -> Result {
+            ensure_inherent(origin)?;
+            Self::check_reporter(&check.payload(), &reporter, &signature)?;
+            let UploadCheck { person, company, order_id, content_hash, size_ok, schema_ok, duplicate } = check;
+            let upload = (person.clone(), company.clone(), order_id);
+            ensure!(Self::is_pending(&upload), "upload is not waiting for validation");
+            let metadata = Self::get_metadata(&person, &company, order_id).ok_or("no data to validate")?;
+            ensure!(metadata.content_hash == content_hash, "report is for another version of the data");
+
+            let report = ValidationReport {
+                content_hash: content_hash,
+                size_ok: size_ok,
+                schema_ok: schema_ok,
+                duplicate: duplicate,
+                reported_at: <system::Module<T>>::block_number(),
+            };
+            let passed = report.passed();
+            Self::dequeue_validation(&upload);
+            <Reports<T>>::insert(&upload, report);
+            Self::deposit_event(RawEvent::DataValidated(person, company, order_id, passed));
+            Ok(())
+        }
+
+        // Companies can ask the chain to flag uploads to an order which were also sent to their other orders.
+        fn set_cross_order_check(origin, order_id: usize, enabled: bool) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::get_orderform(&company, order_id).is_some(), "no orderform");
+            <CrossOrderCheck<T>>::insert(&(company, order_id), enabled);
+            Ok(())
+        }
+
         // Companies can buy the people's data by calling this API.
         fn buy_data(origin, person: T::AccountId, order_id: usize) -> Result {
             let company = ensure_signed(origin)?;
@@ -79,154 +975,1670 @@ decl_module! {
             }
         }
 
-        // When companies find the data is OK, they confirm data to unlock the people's account,
-        // if people do evil (upload an illegal data), companies can call tip_off_data().
-        fn confirm_data(origin, person: T::AccountId, order_id: usize) -> Result {
+        // Companies can change how the uploads of their order are priced. It only affects later purchases.
+        fn set_pricing(origin, order_id: usize, pricing: Pricing<T::Balance, T::BlockNumber>) -> Result {
             let company = ensure_signed(origin)?;
-            ensure!(company != person, "you can't confirm to buy your data");
-            T::Currency::remove_lock(PERSON_LOCK,&person);
-            Self::deposit_event(RawEvent::Confirmed(person,order_id));
+            ensure!(Self::get_orderform(&company, order_id).is_some(), "no orderform");
+            match pricing {
+                Pricing::Tiers(ref tiers) => {
+                    ensure!(!tiers.is_empty() && tiers.len() <= MAX_PRICE_TIERS, "tiers must have between 1 and 8 entries");
+                    ensure!(tiers.windows(2).all(|w| w[0].0 < w[1].0), "tiers must be sorted by submission count");
+                },
+                Pricing::Decay { interval, .. } => ensure!(!interval.is_zero(), "decay interval can't be empty"),
+                _ => {},
+            }
+            let now = <system::Module<T>>::block_number();
+            <OrderPricing<T>>::insert(&(company.clone(), order_id), (pricing, now));
+            Self::deposit_event(RawEvent::PricingChanged(company, order_id));
             Ok(())
         }
 
-        // When company finds that the people did not fill the data in the form as required, call tip_off_data(),
-        // the chain will check the data.
-        // If it is, the chain will punish people by keeping locking his account.
-        // Otherwise, the chain will punish company by locking its account.
-        // Normally, this API will not be called.
-        fn tip_off_data(origin, person: T::AccountId, order_id: usize) -> Result {
-            let company = ensure_signed(origin)?;
-            ensure!(company != person, "you can't tip-off yourself");
-            if let Some(metadata) = Self::get_metadata(&person,&company,order_id){
-                let person_data = Self::get_from_chain(metadata.hash_key);
-                if let Some(order) = Self::get_orderform(&company, order_id){
-                    let is_legal = Self::validate_data(person_data, order.content);
-                    if is_legal {
-                        // the company does evil, we need to lock the company's account or other ways for punishment.
-                        T::Currency::set_lock(COMPANY_LOCK, &company, Bounded::max_value(), <system::Module<T>>::block_number() + One::one() ,WithdrawReasons::all());
-                        T::Currency::remove_lock(PERSON_LOCK, &person);
-                    }
-                    // if person does evil, we need to keep locking the person's account or other ways for punishment.
+        // Companies turn one of their orders into an aggregate order and reserve the budget shared by the contributors,
+        // and a deposit of AGGREGATE_DEPOSIT_BYTES until the aggregate is published or the order expires.
+        // Answers are encrypted to encryption_key, the aggregation key of a reporter, for answer_period blocks.
+        fn open_aggregate(origin, order_id: usize, max_value: u64, buckets: Vec<u64>, epsilon_millis: u32, min_contributors: u32, budget: T::Balance, answer_period: T::BlockNumber, encryption_key: [u8; 32]) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::get_orderform(&company, order_id).is_some(), "no orderform");
+            let key = (company.clone(), order_id);
+            ensure!(Self::aggregate_order(&key).is_none(), "order is already an aggregate order");
+            ensure!(buckets.len() <= MAX_BUCKETS, "too many buckets");
+            ensure!(buckets.windows(2).all(|w| w[0] < w[1]), "buckets must be ascending");
+            let parameters = Self::parameters();
+            ensure!(epsilon_millis > 0, "epsilon can't be zero");
+            ensure!(epsilon_millis <= parameters.max_epsilon_millis, "epsilon is too large");
+            ensure!(min_contributors >= parameters.min_contributors, "too few contributors to hide anyone");
+            ensure!(min_contributors <= MAX_CONTRIBUTORS, "an aggregate can't have that many contributors");
+            ensure!(!budget.is_zero(), "budget can't be zero");
+            ensure!(!answer_period.is_zero(), "answer window can't be empty");
+            ensure!(answer_period <= parameters.max_answer_period, "answer window is too long");
+            let reporter = Self::aggregation_key_owner(&encryption_key).ok_or("unknown aggregation key")?;
+            ensure!(T::Reporters::is_reporter(&reporter), "aggregation key does not belong to a reporter");
+            ensure!(Self::pending_aggregates().len() < MAX_PENDING_AGGREGATES, "too many aggregates are waiting for a result");
+
+            let deposit = Self::deposit_per_byte() * <T::Balance as As<u64>>::sa(AGGREGATE_DEPOSIT_BYTES);
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&company, budget + deposit)?;
+            let answer_end = <system::Module<T>>::block_number() + answer_period;
+            let report_end = answer_end + parameters.aggregate_report_period;
+            <AggregateOrders<T>>::insert(&key, AggregateOrder {
+                encryption_key: encryption_key,
+                max_value: max_value,
+                buckets: buckets,
+                epsilon_millis: epsilon_millis,
+                min_contributors: min_contributors,
+                budget: budget,
+                answer_end: answer_end,
+                report_end: report_end,
+                deposit: deposit,
+            });
+            <AggregateEnds<T>>::mutate(report_end, |keys| keys.push(key.clone()));
+            <PendingAggregates<T>>::mutate(|pending| pending.push(key));
+            Self::deposit_event(RawEvent::AggregateOpened(company, order_id, budget));
+            Ok(())
+        }
+
+        // People submit their answer encrypted to the aggregation key of the order, see AggregateOrder.
+        fn submit_answer(origin, to_company: T::AccountId, order_id: usize, answer: Vec<u8>) -> Result {
+            let person = ensure_signed(origin)?;
+            let key = (to_company.clone(), order_id);
+            let aggregate = Self::aggregate_order(&key).ok_or("no aggregate order")?;
+            ensure!(<system::Module<T>>::block_number() < aggregate.answer_end, "answer window is over");
+            ensure!(answer.len() == ANSWER_LEN, "answer is not an encrypted value");
+            let order = Self::get_orderform(&to_company, order_id).ok_or("no orderform")?;
+            ensure!(Self::is_eligible(&person, &order), "you are not eligible for this order");
+            let answer_key = (to_company.clone(), order_id, person.clone());
+            ensure!(Self::answer_index(&answer_key).is_none(), "you already answered");
+            let index = Self::answer_count(&key);
+            ensure!(index < MAX_CONTRIBUTORS, "aggregate order has all the answers it can take");
+
+            <Answers<T>>::insert(&(to_company.clone(), order_id, index), (person.clone(), answer));
+            <AnswerCount<T>>::insert(&key, index + 1);
+            <AnswerIndex<T>>::insert(&answer_key, index);
+            Self::deposit_event(RawEvent::AnswerSubmitted(person, to_company, order_id));
+            Ok(())
+        }
 
-                    Self::deposit_event(RawEvent::TippedOff(is_legal));
-                }else {
-                    return Err("no orderform");
+        // Companies can buy every upload of an order they did not buy yet. At most max_count uploads
+        // (and never more than MAX_BULK_PURCHASE) are gone through per call, each one tried is charged BULK_ITEM_BYTES,
+        // and the next call carries on from there. Uploads being auctioned are tried again by the next call,
+        // erased uploads and the company's own are skipped.
+        fn buy_all(origin, order_id: usize, max_count: u32) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::get_orderform(&company, order_id).is_some(), "no orderform");
+            let limit = rstd::cmp::min(max_count as usize, MAX_BULK_PURCHASE);
+            let key = (company.clone(), order_id);
+            let count = Self::uploader_count(&key);
+
+            // everything before the cursor is settled for good
+            let mut cursor = Self::buy_cursor(&key);
+            let mut next = cursor;
+            let mut tried = 0;
+            let mut bought: u32 = 0;
+            while next < count && tried < limit {
+                tried += 1;
+                let settled = match Self::uploader_at(&(company.clone(), order_id, next)) {
+                    // erased, or the company's own upload it can't buy
+                    None => true,
+                    Some(ref person) if *person == company => true,
+                    Some(person) => {
+                        if Self::charge_for_bytes(&company, BULK_ITEM_BYTES).is_err() {
+                            break;
+                        }
+                        let upload = (person.clone(), company.clone(), order_id);
+                        match Self::transfer_data(company.clone(), person.clone(), order_id) {
+                            Ok(()) => {
+                                bought += 1;
+                                true
+                            },
+                            // the auction may fail, then the upload can still be bought
+                            Err(_) if Self::get_auction(&upload).is_some() => false,
+                            // the company can't pay for more, this upload is tried again next time
+                            Err(_) if !Self::can_buy(&company, &person, order_id) => break,
+                            // bought, sold exclusively or erased
+                            Err(_) => true,
+                        }
+                    },
                 };
-                Ok(())
-            }else {
-                Err("no data to tip-off")
+                next += 1;
+                if settled && cursor + 1 == next {
+                    cursor = next;
+                }
             }
-
+            <BuyCursor<T>>::insert(&key, cursor);
+            Self::deposit_event(RawEvent::BulkPurchased(company, order_id, bought));
+            Ok(())
         }
 
-        // Company publishes its order form onto chain for every people to have a look.
-        fn publish_order(origin, order_name: Vec<u8>, content: Vec<u8>, unit_price: T::Balance) -> Result{
+        // Companies can buy the uploads of the given people for an order in one call.
+        fn buy_batch(origin, order_id: usize, people: Vec<T::AccountId>) -> Result {
             let company = ensure_signed(origin)?;
-            let is_existed = <Company<T>>::exists(&company);
-            if !is_existed {
-                <Company<T>>::insert(company.clone(), Vec::new());
+            ensure!(people.len() <= MAX_BULK_PURCHASE, "too many people in one batch");
+            ensure!(<Company<T>>::exists(&company), "no company");
+
+            let mut bought: u32 = 0;
+            for person in people {
+                if Self::charge_for_bytes(&company, BULK_ITEM_BYTES).is_err() {
+                    break;
+                }
+                if person != company && Self::transfer_data(company.clone(), person, order_id).is_ok() {
+                    bought += 1;
+                }
             }
-            let mut order_list = Self::get_order(&company);
-            let new_order: OrderForm<T::Balance> = OrderForm{
-                id: order_list.len(),
-                order_name: order_name,
-                content: content,
-                unit_price: unit_price,
-            };
-            order_list.push(new_order);
+            Self::deposit_event(RawEvent::BulkPurchased(company, order_id, bought));
             Ok(())
         }
 
-        // People can choose to upload their own data onto the chain for order form which they are interested in.
-        fn upload_data(origin, data_name: Vec<u8>, data_content: Vec<u8>, to_company: T::AccountId, order_id: usize) -> Result {
-            let person = ensure_signed(origin)?;
-            let is_existed = <People<T>>::exists(&to_company);
-            if !is_existed {
-                <People<T>>::insert(person.clone(), Vec::new());
-            }
-            let mut metadata_list = Self::get_data(&person);
-            let hash_key = Self::add_to_chain(data_content);
-            let new_data: DataMetadata<T::AccountId> = DataMetadata{
-                hash_key: hash_key,
-                data_name: data_name,
-                to_company: to_company,
-                order_id: order_id,
-            };
-            metadata_list.push(new_data);
+        // Companies set what percentage of the price they pay for each rating, e.g. [20, 40, 60, 80, 100].
+        // A better rating never pays less and every rating pays at least MIN_RATING_PERCENTAGE.
+        // The table only applies to data bought after it is set.
+        fn set_rating_table(origin, order_id: usize, percentages: Vec<u8>) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::get_orderform(&company, order_id).is_some(), "no orderform");
+            ensure!(percentages.len() == MAX_RATING as usize, "rating table needs one percentage per rating");
+            ensure!(percentages.iter().all(|&p| p <= 100), "percentage can't exceed 100");
+            ensure!(percentages.iter().all(|&p| p >= MIN_RATING_PERCENTAGE), "percentage is below the minimum payout");
+            ensure!(percentages.windows(2).all(|w| w[0] <= w[1]), "a better rating can't pay less");
+            <RatingTable<T>>::insert(&(company, order_id), percentages);
             Ok(())
         }
 
-        // People can update their own data when they find somethine changed.
-        fn update_data(origin, data_name: Vec<u8>, data_content: Vec<u8>, to_company: T::AccountId, order_id: usize) -> Result {
-            let person = ensure_signed(origin)?;
-            let is_existed = <People<T>>::exists(&to_company);
-            if !is_existed {
-                <People<T>>::insert(person.clone(), Vec::new());
-                Err("no person")
-            }else {
-                let metadata_list = Self::get_data(&person);
-                for mut metadata in metadata_list {
-                    if metadata.to_company == to_company && metadata.order_id == order_id {
-                        let hash_key = Self::add_to_chain(data_content);
-                        metadata.hash_key = hash_key;
-                        metadata.data_name = data_name;
-                        break;
-                    }
-                };
-                Ok(())
-            }
+        // Companies set what their buyers pay to get a newer version of data they bought for an order.
+        fn set_upgrade_price(origin, order_id: usize, price: T::Balance) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::get_orderform(&company, order_id).is_some(), "no orderform");
+            <UpgradePrice<T>>::insert(&(company, order_id), price);
+            Ok(())
+        }
+
+        // Buyers of a confirmed deal can buy the latest version of the data at the upgrade price.
+        // The upgrade is a new deal for the new version, held in escrow until it is confirmed.
+        fn buy_upgrade(origin, deal_id: u64) -> Result {
+            let company = ensure_signed(origin)?;
+            let deal = Self::get_deal(deal_id).ok_or("no deal")?;
+            ensure!(deal.buyer == company, "you did not buy this data");
+            ensure!(deal.status == DealStatus::Confirmed, "only confirmed deals can be upgraded");
+            let (person, order_id) = (deal.person.clone(), deal.order_id);
+            let metadata = Self::get_metadata(&person, &deal.to_company, order_id).ok_or("no data to buy")?;
+            let latest = Self::versions(&(person.clone(), deal.to_company.clone(), order_id)).len() as u32;
+            ensure!(deal.version + 1 < latest, "you already have the latest version");
+            Self::ensure_can_sell(&company, &(person.clone(), deal.to_company.clone(), order_id))?;
 
+            let price = match Self::upgrade_price(&(deal.to_company.clone(), order_id)) {
+                Some(price) => price,
+                None => Self::current_price(&deal.to_company, &person, order_id).ok_or("no orderform")?,
+            };
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&company, price)?;
+            Self::open_deal(company, person, &metadata, price);
+            Ok(())
         }
 
-    }
-}
+        // Companies list the fields which uploads of an order have to disclose with upload_fields().
+        fn set_mandatory_fields(origin, order_id: usize, fields: Vec<u32>) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::get_orderform(&company, order_id).is_some(), "no orderform");
+            ensure!(fields.len() <= MAX_FIELDS, "too many fields");
+            <MandatoryFields<T>>::insert(&(company, order_id), fields);
+            Ok(())
+        }
 
-impl<T: Trait> Module<T> {
+        // When companies find the data is OK, they confirm data with a rating from 1 to 5 to release the escrow
+        // and unlock the people's account. The person gets the share of the price the order's rating table
+        // gave for the rating when the data was bought, the rest goes back to the company.
+        // if people do evil (upload an illegal data), companies can call tip_off_data().
+        fn confirm_data(origin, deal_id: u64, rating: u8) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(rating >= 1 && rating <= MAX_RATING, "rating must be between 1 and 5");
+            let mut deal = Self::get_deal(deal_id).ok_or("no deal to confirm")?;
+            ensure!(deal.buyer == company, "you did not buy this data");
+            ensure!(deal.status == DealStatus::Paid, "deal is already settled");
+            let person = deal.person.clone();
 
-    fn transfer_data(company: T::AccountId, person: T::AccountId, order_id: usize) -> Result{
-        let orders = Self::get_order(&company);
+            let percentage = deal.rating_table.get((rating - 1) as usize).cloned().unwrap_or(100);
+            let payout = deal.price * <T::Balance as As<u64>>::sa(percentage as u64) / <T::Balance as As<u64>>::sa(100);
+            <balances::Module<T> as ReservableCurrency<_>>::repatriate_reserved(&company, &person, payout)?;
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&company, deal.price - payout);
 
-        let metadata_list = Self::get_data(&person);
-        for metadata in metadata_list {
-            if metadata.to_company == company && metadata.order_id == order_id {
-
-                for order in &orders {
-                    if order.id == order_id {
-                        let data = Self::get_from_chain(metadata.hash_key);
-                        let pay = order.unit_price;
-                        <balances::Module<T> as Currency<_>>::transfer(&company, &person, pay)?;
-                        T::Currency::set_lock(PERSON_LOCK, &person, Bounded::max_value(),  <system::Module<T>>::block_number() + One::one(), WithdrawReasons::all());
-                        Self::deposit_event(RawEvent::Transfered(data));
-                    }
-                }
+            deal.status = DealStatus::Confirmed;
+            deal.rating = rating;
+            <Deals<T>>::insert(deal_id, deal);
+            <PaidDeals<T>>::mutate(&company, |n| *n = n.saturating_sub(1));
+            <Reputation<T>>::mutate(&person, |(count, sum)| {
+                *count += 1;
+                *sum += rating as u32;
+            });
+            T::Currency::remove_lock(PERSON_LOCK,&person);
+            Self::deposit_event(RawEvent::DealConfirmed(deal_id, rating, payout));
+            Ok(())
+        }
 
-            }
-        };
+        // The data itself is handed over off-chain, buyers acknowledge the content hash they received here.
+        fn acknowledge_delivery(origin, deal_id: u64, content_hash: T::Hash) -> Result {
+            let company = ensure_signed(origin)?;
+            let deal = Self::get_deal(deal_id).ok_or("no deal")?;
+            ensure!(deal.buyer == company, "you did not buy this data");
+            Self::record_receipt(deal_id, deal, content_hash)
+        }
 
-        Ok(())
-    }
+        // People can submit a receipt the buyer signed off-chain, over the encoded
+        // (DELIVERY_CONTEXT, deal_id, content_hash).
+        fn submit_receipt(origin, deal_id: u64, content_hash: T::Hash, signature: T::Signature) -> Result {
+            let person = ensure_signed(origin)?;
+            let deal = Self::get_deal(deal_id).ok_or("no deal")?;
+            ensure!(deal.person == person, "you did not sell this data");
+            let message = (DELIVERY_CONTEXT, deal_id, content_hash).encode();
+            ensure!(signature.verify(&message[..], &deal.buyer), "invalid receipt signature");
+            Self::record_receipt(deal_id, deal, content_hash)
+        }
 
-    // store data which people upload onto the chain.
-    fn add_to_chain(value: Vec<u8>) -> u64
-    {
-        let hash_key= Self::get_n();
-        <Data<T>>::insert(&hash_key,value);
-        <Nonce<T>>::mutate(|n| *n += 1);
-        hash_key
-    }
+        // When company finds that the people did not fill the data in the form as required, call tip_off_data(),
+        // which opens a dispute. After evidence_period blocks the chain will check the data, or the council
+        // will decide if either side submitted evidence.
+        // If it is, the chain will punish people by keeping locking his account.
+        // Otherwise, the chain will punish company by locking its account.
+        // Normally, this API will not be called.
+        fn tip_off_data(origin, deal_id: u64) -> Result {
+            let company = ensure_signed(origin)?;
+            let deal = Self::get_deal(deal_id).ok_or("no data to tip-off")?;
+            ensure!(deal.buyer == company, "you did not buy this data");
+            ensure!(deal.status == DealStatus::Paid, "deal is already settled");
+            Self::open_dispute(deal_id, company, None);
+            Ok(())
+        }
 
-    fn get_from_chain(key: u64) -> Vec<u8> {
-        Self::get_content(key)
-    }
+        // The losing side of a dispute can appeal the verdict within appeal_period blocks, reserving appeal_bond.
+        // The council then has appeal_voting_period blocks to pass a motion overturning it (see decide_appeal()). If it
+        // does, the settlement and the penalty are reversed and the bond is returned, otherwise the bond goes to the winner.
+        fn appeal(origin, deal_id: u64) -> Result {
+            let appellant = ensure_signed(origin)?;
+            let deal = Self::get_deal(deal_id).ok_or("no deal")?;
+            let dispute = Self::get_dispute(deal_id).ok_or("no dispute")?;
+            let (winner, decided_at) = dispute.verdict.ok_or("dispute has no verdict yet")?;
+            ensure!((appellant == deal.buyer || appellant == deal.person) && appellant != winner, "only the losing side can appeal");
+            let now = <system::Module<T>>::block_number();
+            ensure!(now < decided_at + Self::appeal_period(), "appeal period is over");
+            ensure!(Self::get_appeal(deal_id).is_none(), "verdict was already appealed");
 
-    // Brief Implementation:
-    // just validate if data is empty and data equals order
-    fn validate_data(data: Vec<u8>, order: Vec<u8>) -> bool {
-        data != order && !data.is_empty()
-    }
+            let bond = Self::appeal_bond();
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&appellant, bond)?;
+            let voting_end = now + Self::appeal_voting_period();
+            <Appeals<T>>::insert(deal_id, Appeal {
+                appellant: appellant.clone(),
+                bond: bond,
+                voting_end: voting_end,
+                overturned: None,
+            });
+            <AppealEnds<T>>::mutate(voting_end, |deal_ids| deal_ids.push(deal_id));
+            <OpenAppeals<T>>::mutate(&deal.buyer, |n| *n += 1);
+            Self::deposit_event(RawEvent::AppealFiled(appellant, deal_id));
+            Ok(())
+        }
 
-    fn get_metadata(person: &T::AccountId, company: &T::AccountId, order_id: usize) -> Option<DataMetadata<T::AccountId>> {
-        let mut data_metadata: Option<DataMetadata<T::AccountId>> = None;
+        // The council decides an appeal with a motion passed by its majority, before voting_end.
+        fn decide_appeal(origin, deal_id: u64, overturn: bool) -> Result {
+            T::Governance::ensure_majority(origin)?;
+            let appeal = Self::get_appeal(deal_id).ok_or("no appeal")?;
+            ensure!(appeal.overturned.is_none(), "appeal was already decided");
+            ensure!(<system::Module<T>>::block_number() < appeal.voting_end, "voting on the appeal is over");
+            Self::close_appeal(deal_id, overturn);
+            Ok(())
+        }
+
+        // The council decides a dispute referred to it with a motion passed by its majority, for the person
+        // or for the buyer. Without a decision before council_end the dispute is judged by the automatic check.
+        fn decide_dispute(origin, deal_id: u64, for_person: bool) -> Result {
+            T::Governance::ensure_majority(origin)?;
+            let dispute = Self::get_dispute(deal_id).ok_or("no dispute")?;
+            ensure!(dispute.verdict.is_none(), "dispute was already decided");
+            let council_end = dispute.council_end.ok_or("dispute was not referred to the council")?;
+            ensure!(<system::Module<T>>::block_number() < council_end, "council's time to decide is over");
+            Self::settle_dispute(deal_id, Some(for_person));
+            Ok(())
+        }
+
+        // Both sides of a dispute can submit evidence until its evidence window closes. Evidence sends the
+        // dispute to the council, which weighs it instead of the automatic check.
+        fn submit_evidence(origin, deal_id: u64, evidence_hash: T::Hash, statement: Vec<u8>) -> Result {
+            let who = ensure_signed(origin)?;
+            let deal = Self::get_deal(deal_id).ok_or("no deal")?;
+            ensure!(who == deal.buyer || who == deal.person, "you are not part of this dispute");
+            let mut dispute = Self::get_dispute(deal_id).ok_or("no dispute")?;
+            ensure!(dispute.verdict.is_none() && <system::Module<T>>::block_number() < dispute.evidence_end, "evidence window is closed");
+            ensure!(statement.len() <= MAX_STATEMENT_LEN, "statement is too long");
+            ensure!(dispute.evidence.len() < MAX_EVIDENCE, "too much evidence");
+            Self::charge_for_bytes(&who, statement.len())?;
+
+            dispute.evidence.push(Evidence {
+                from: who.clone(),
+                evidence_hash: evidence_hash,
+                statement: statement,
+                submitted_at: <system::Module<T>>::block_number(),
+            });
+            <Disputes<T>>::insert(deal_id, dispute);
+            Self::deposit_event(RawEvent::EvidenceSubmitted(who, deal_id, evidence_hash));
+            Ok(())
+        }
+
+        // People dispute a purchase the buyer keeps in escrow for longer than delivery_period, or abuses.
+        // The dispute runs the same way as for tip_off_data(), and a company which loses also pays
+        // dispute_penalty from its bond to the person.
+        fn dispute_company(origin, deal_id: u64, reason: DisputeReason) -> Result {
+            let person = ensure_signed(origin)?;
+            let deal = Self::get_deal(deal_id).ok_or("no deal to dispute")?;
+            ensure!(deal.person == person, "you did not sell this data");
+            ensure!(deal.status == DealStatus::Paid, "deal is already settled");
+            if reason == DisputeReason::NeverConfirmed {
+                ensure!(<system::Module<T>>::block_number() >= deal.purchased_at + Self::delivery_period(), "company still has time to confirm");
+            }
+            Self::deposit_event(RawEvent::CompanyDisputed(deal_id, reason));
+            Self::open_dispute(deal_id, person, Some(reason));
+            Ok(())
+        }
+
+        // Companies reserve a bond, they need min_company_bond to buy data.
+        fn post_bond(origin, amount: T::Balance) -> Result {
+            let company = ensure_signed(origin)?;
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&company, amount)?;
+            let bond = Self::company_bond(&company) + amount;
+            <CompanyBond<T>>::insert(&company, bond);
+            Self::deposit_event(RawEvent::BondChanged(company, bond));
+            Ok(())
+        }
+
+        // Companies get their bond back once none of their deals is held in escrow and no verdict on them
+        // can still be appealed or is being appealed.
+        fn withdraw_bond(origin, amount: T::Balance) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::paid_deals(&company) == 0, "deals are still held in escrow");
+            ensure!(Self::open_appeals(&company) == 0, "appeals are still open");
+            ensure!(<system::Module<T>>::block_number() >= Self::appealable_until(&company), "a verdict can still be appealed");
+            let bond = Self::company_bond(&company);
+            ensure!(amount <= bond, "bond is too small");
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&company, amount);
+            <CompanyBond<T>>::insert(&company, bond - amount);
+            Self::deposit_event(RawEvent::BondChanged(company, bond - amount));
+            Ok(())
+        }
+
+        // Companies turn one of their orders into a recurring one, paying price for fresh data every period.
+        fn offer_subscription(origin, order_id: usize, period: T::BlockNumber, price: T::Balance) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::get_orderform(&company, order_id).is_some(), "no orderform");
+            ensure!(!period.is_zero(), "period can't be empty");
+            <SubscriptionOffers<T>>::insert(&(company.clone(), order_id), SubscriptionTerms {
+                period: period,
+                price: price,
+            });
+            Self::deposit_event(RawEvent::SubscriptionOffered(company, order_id, price, period));
+            Ok(())
+        }
+
+        // Companies reserve the allowance subscribers of an order are paid from.
+        fn fund_subscriptions(origin, order_id: usize, amount: T::Balance) -> Result {
+            let company = ensure_signed(origin)?;
+            ensure!(Self::subscription_terms(&(company.clone(), order_id)).is_some(), "no subscription offer");
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&company, amount)?;
+            <Allowances<T>>::mutate(&(company, order_id), |allowance| *allowance += amount);
+            Ok(())
+        }
+
+        // Companies take back what is left of the allowance of an order, subscribers are only paid from what remains.
+        fn withdraw_allowance(origin, order_id: usize, amount: T::Balance) -> Result {
+            let company = ensure_signed(origin)?;
+            let key = (company.clone(), order_id);
+            let allowance = Self::allowance(&key);
+            ensure!(amount <= allowance, "allowance is too small");
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&company, amount);
+            <Allowances<T>>::insert(&key, allowance - amount);
+            Ok(())
+        }
+
+        // People opt in to a recurring order they uploaded data for. From then on they are paid at the end
+        // of every period in which they submitted fresh data through update_data().
+        fn subscribe(origin, to_company: T::AccountId, order_id: usize) -> Result {
+            let person = ensure_signed(origin)?;
+            let terms = Self::subscription_terms(&(to_company.clone(), order_id)).ok_or("no subscription offer")?;
+            ensure!(Self::get_metadata(&person, &to_company, order_id).is_some(), "upload data before you subscribe");
+            let key = (person.clone(), to_company.clone(), order_id);
+            ensure!(Self::get_subscription(&key).is_none(), "you already subscribed");
+
+            let period_end = <system::Module<T>>::block_number() + terms.period;
+            <PeriodEnds<T>>::mutate(period_end, |ends| ends.push(key.clone()));
+            <Subscriptions<T>>::insert(&key, Subscription {
+                period_end: period_end,
+                submitted: false,
+                paid_periods: 0,
+                missed_periods: 0,
+            });
+            Self::deposit_event(RawEvent::Subscribed(person, to_company, order_id));
+            Ok(())
+        }
+
+        // Either side can end a subscription: a person passes the company, a company passes the person.
+        fn cancel_subscription(origin, counterparty: T::AccountId, order_id: usize) -> Result {
+            let who = ensure_signed(origin)?;
+            let as_person = (who.clone(), counterparty.clone(), order_id);
+            let as_company = (counterparty, who, order_id);
+            if Self::get_subscription(&as_person).is_some() {
+                Self::end_subscription(as_person);
+            } else if Self::get_subscription(&as_company).is_some() {
+                Self::end_subscription(as_company);
+            } else {
+                return Err("no subscription");
+            }
+            Ok(())
+        }
+
+        // People can put an upload up for a sealed-bid auction instead of selling it to the order's company.
+        // Bids are committed for commit_period blocks and revealed during the following reveal_period blocks.
+        fn list_exclusive(origin, to_company: T::AccountId, order_id: usize, reserve_price: T::Balance, commit_period: T::BlockNumber, reveal_period: T::BlockNumber) -> Result {
+            let person = ensure_signed(origin)?;
+            ensure!(Self::get_metadata(&person, &to_company, order_id).is_some(), "no data to auction");
+            let upload = (person.clone(), to_company.clone(), order_id);
+            ensure!(Self::get_auction(&upload).is_none(), "data is already being auctioned");
+            ensure!(Self::exclusive_to(&upload).is_none(), "data was sold exclusively");
+            ensure!(Self::deal_id(&(to_company.clone(), person.clone(), to_company.clone(), order_id)).is_none(), "data was already sold");
+            ensure!(!commit_period.is_zero() && !reveal_period.is_zero(), "auction windows can't be empty");
+
+            let now = <system::Module<T>>::block_number();
+            let auction = Auction {
+                reserve_price: reserve_price,
+                bond: rstd::cmp::max(reserve_price, Self::min_bid_deposit()),
+                commit_end: now + commit_period,
+                reveal_end: now + commit_period + reveal_period,
+                best_bid: None,
+            };
+            <AuctionEnds<T>>::mutate(auction.reveal_end, |ends| ends.push(upload.clone()));
+            <Auctions<T>>::insert(&upload, auction);
+            Self::deposit_event(RawEvent::AuctionStarted(person, to_company, order_id, reserve_price));
+            Ok(())
+        }
+
+        // Companies commit to a bid with hash((amount, salt)), reserving the bond of the auction.
+        // The bond is lost to the person if the bid is never revealed. An auction takes at most MAX_BIDDERS bids.
+        fn commit_bid(origin, person: T::AccountId, to_company: T::AccountId, order_id: usize, commitment: T::Hash) -> Result {
+            let bidder = ensure_signed(origin)?;
+            ensure!(bidder != person, "you can't bid for your own data");
+            let upload = (person.clone(), to_company.clone(), order_id);
+            let auction = Self::get_auction(&upload).ok_or("no auction")?;
+            ensure!(<system::Module<T>>::block_number() < auction.commit_end, "commit window is over");
+            let bid_key = (person.clone(), to_company.clone(), order_id, bidder.clone());
+            ensure!(Self::get_commitment(&bid_key).is_none(), "you already bid");
+            ensure!(Self::bidders(&upload).len() < MAX_BIDDERS, "too many bidders");
+
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&bidder, auction.bond)?;
+            <Commitments<T>>::insert(&bid_key, commitment);
+            <Bidders<T>>::mutate(&upload, |bidders| bidders.push(bidder.clone()));
+            Self::deposit_event(RawEvent::BidCommitted(person, to_company, order_id, bidder));
+            Ok(())
+        }
+
+        // Companies reveal their bid after the commit window. The bond is returned, and the bid amount stays
+        // reserved as long as it is the highest one.
+        fn reveal_bid(origin, person: T::AccountId, to_company: T::AccountId, order_id: usize, amount: T::Balance, salt: T::Hash) -> Result {
+            let bidder = ensure_signed(origin)?;
+            let upload = (person.clone(), to_company.clone(), order_id);
+            let mut auction = Self::get_auction(&upload).ok_or("no auction")?;
+            let now = <system::Module<T>>::block_number();
+            ensure!(now >= auction.commit_end, "reveal window has not started");
+            ensure!(now < auction.reveal_end, "reveal window is over");
+            let bid_key = (person.clone(), to_company.clone(), order_id, bidder.clone());
+            let commitment = Self::get_commitment(&bid_key).ok_or("no bid to reveal")?;
+            ensure!(T::Hashing::hash_of(&(amount, salt)) == commitment, "bid does not match commitment");
+
+            <Commitments<T>>::remove(&bid_key);
+            <Bidders<T>>::mutate(&upload, |bidders| bidders.retain(|b| b != &bidder));
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&bidder, auction.bond);
+
+            let beats_best = match auction.best_bid {
+                Some((_, best)) => amount > best,
+                None => amount >= auction.reserve_price,
+            };
+            if beats_best && <balances::Module<T> as ReservableCurrency<_>>::reserve(&bidder, amount).is_ok() {
+                if let Some((previous, previous_amount)) = auction.best_bid.take() {
+                    <balances::Module<T> as ReservableCurrency<_>>::unreserve(&previous, previous_amount);
+                }
+                auction.best_bid = Some((bidder.clone(), amount));
+                <Auctions<T>>::insert(&upload, auction);
+            }
+            Self::deposit_event(RawEvent::BidRevealed(person, to_company, order_id, bidder, amount));
+            Ok(())
+        }
+
+        // Company publishes its order form onto chain for every people to have a look.
+        // The company pays byte_fee for every byte of order_name and content.
+        fn publish_order(origin, order_name: Vec<u8>, content: Vec<u8>, unit_price: T::Balance, requirements: Vec<Claim>, eligibility: Vec<PredicateOp>, category: Vec<u8>, tags: Vec<Vec<u8>>) -> Result{
+            let company = ensure_signed(origin)?;
+            ensure!(!Self::parameters().require_company_approval || Self::is_approved_company(&company), "company is not approved");
+            ensure!(order_name.len() <= MAX_NAME_LEN, "order name is too long");
+            ensure!(content.len() <= Self::max_order_content_size() as usize, "order content is too large");
+            ensure!(requirements.len() <= MAX_CLAIMS, "too many requirements");
+            ensure!(requirements.iter().all(Claim::is_well_formed), "malformed requirement");
+            ensure!(<attestation::Module<T>>::is_well_formed(&eligibility), "invalid eligibility predicate");
+            ensure!(category.len() <= MAX_LABEL_LEN, "category is too long");
+            ensure!(tags.len() <= MAX_TAGS, "too many tags");
+            ensure!(tags.iter().all(|tag| tag.len() <= MAX_LABEL_LEN), "tag is too long");
+            Self::charge_for_bytes(&company, order_name.len() + content.len())?;
+
+            Self::insert_order(company, order_name, content, unit_price, requirements, eligibility, category, tags);
+            Ok(())
+        }
+
+        // People can choose to upload their own data onto the chain for order form which they are interested in.
+        // The person pays byte_fee for every byte of data_name and data_content.
+        // Everyone can upload once per order, and only if they are attested for what the order requires.
+        fn upload_data(origin, data_name: Vec<u8>, data_content: Vec<u8>, to_company: T::AccountId, order_id: usize) -> Result {
+            let person = ensure_signed(origin)?;
+            ensure!(Self::get_metadata(&person, &to_company, order_id).is_none(), "you already uploaded data to this order");
+            let order = Self::get_orderform(&to_company, order_id).ok_or("no orderform")?;
+            ensure!(Self::is_eligible(&person, &order), "you are not eligible for this order");
+            ensure!(data_name.len() <= MAX_NAME_LEN, "data name is too long");
+            ensure!(data_content.len() <= Self::max_data_size() as usize, "data is too large");
+            let content_hash = T::Hashing::hash(&data_content);
+            ensure!(Self::order_content(&(to_company.clone(), order_id, content_hash)).is_none(), "same data was already uploaded to this order");
+            let deposit = Self::pay_for_upload(&person, data_name.len() + data_content.len(), data_content.len())?;
+
+            Self::insert_upload(person, data_name, data_content, deposit, to_company, order_id);
+            Ok(())
+        }
+
+        // People can upload only some fields of their data. The upload commits to field_root, the merkle root
+        // (see merkle_root()) of field_leaf() of every field, and each disclosed field comes with its
+        // inclusion proof: the sibling hashes from the leaf up to the root.
+        // Only the disclosed fields are stored and sold through buy_data() like any other upload.
+        fn upload_fields(origin, data_name: Vec<u8>, to_company: T::AccountId, order_id: usize, field_root: T::Hash, fields: Vec<(u32, Vec<u8>)>, proofs: Vec<Vec<T::Hash>>) -> Result {
+            let person = ensure_signed(origin)?;
+            ensure!(Self::get_metadata(&person, &to_company, order_id).is_none(), "you already uploaded data to this order");
+            let order = Self::get_orderform(&to_company, order_id).ok_or("no orderform")?;
+            ensure!(Self::is_eligible(&person, &order), "you are not eligible for this order");
+            ensure!(data_name.len() <= MAX_NAME_LEN, "data name is too long");
+            ensure!(!fields.is_empty() && fields.len() <= MAX_FIELDS, "invalid number of fields");
+            ensure!(fields.len() == proofs.len(), "every field needs a proof");
+            ensure!(fields.iter().zip(proofs.iter()).all(|((index, value), proof)| Self::verify_field(field_root, *index, value, proof)),
+                "field is not part of the root");
+            let disclosed: Vec<u32> = fields.iter().map(|(index, _)| *index).collect();
+            ensure!(Self::mandatory_fields(&(to_company.clone(), order_id)).iter().all(|f| disclosed.contains(f)),
+                "mandatory field is not disclosed");
+
+            let data_content = fields.encode();
+            ensure!(data_content.len() <= Self::max_data_size() as usize, "data is too large");
+            let content_hash = T::Hashing::hash(&data_content);
+            ensure!(Self::order_content(&(to_company.clone(), order_id, content_hash)).is_none(), "same data was already uploaded to this order");
+            let proof_len = proofs.iter().map(|p| p.len()).sum::<usize>() * field_root.as_ref().len();
+            let deposit = Self::pay_for_upload(&person, data_name.len() + data_content.len() + proof_len, data_content.len())?;
+
+            let count = disclosed.len() as u32;
+            <FieldCommitments<T>>::insert(&(person.clone(), to_company.clone(), order_id), (field_root, disclosed));
+            Self::insert_upload(person.clone(), data_name, data_content, deposit, to_company.clone(), order_id);
+            Self::deposit_event(RawEvent::FieldsDisclosed(person, to_company, order_id, count));
+            Ok(())
+        }
+
+        // People start an upload too large for upload_data() by announcing its size, its number of chunks
+        // and the merkle root (see merkle_root()) of chunk_leaf() of every chunk.
+        fn begin_upload(origin, data_name: Vec<u8>, to_company: T::AccountId, order_id: usize, total_size: u32, chunk_count: u32, root_hash: T::Hash) -> Result {
+            let person = ensure_signed(origin)?;
+            ensure!(Self::get_metadata(&person, &to_company, order_id).is_none(), "you already uploaded data to this order");
+            let order = Self::get_orderform(&to_company, order_id).ok_or("no orderform")?;
+            ensure!(Self::is_eligible(&person, &order), "you are not eligible for this order");
+            ensure!(data_name.len() <= MAX_NAME_LEN, "data name is too long");
+            ensure!(total_size > 0 && total_size <= Self::max_upload_size(), "data is too large");
+            ensure!(chunk_count > 0 && chunk_count <= total_size && chunk_count <= MAX_CHUNKS, "invalid chunk count");
+            // every chunk has to fit in one extrinsic
+            ensure!((total_size - 1) / chunk_count < Self::max_data_size(), "chunks are too large");
+            Self::charge_for_bytes(&person, data_name.len())?;
+
+            let upload_id = Self::upload_nonce();
+            let expires_at = <system::Module<T>>::block_number() + Self::upload_timeout();
+            <ChunkedUploads<T>>::insert(upload_id, ChunkedUpload {
+                owner: person.clone(),
+                data_name: data_name,
+                to_company: to_company.clone(),
+                order_id: order_id,
+                total_size: total_size,
+                chunk_count: chunk_count,
+                root_hash: root_hash,
+                chunks_received: 0,
+                received: 0,
+                deposit: Zero::zero(),
+                expires_at: expires_at,
+            });
+            <UploadTimeouts<T>>::mutate(expires_at, |ids| ids.push(upload_id));
+            <UploadNonce<T>>::mutate(|n| *n += 1);
+            Self::deposit_event(RawEvent::UploadStarted(person, to_company, order_id, upload_id));
+            Ok(())
+        }
+
+        // People send one chunk of an upload, paying byte_fee and reserving deposit_per_byte for it and
+        // CHUNK_DEPOSIT_BYTES more. Sending a chunk again replaces it.
+        fn upload_chunk(origin, upload_id: u64, index: u32, bytes: Vec<u8>) -> Result {
+            let person = ensure_signed(origin)?;
+            let mut upload = Self::chunked_upload(upload_id).ok_or("no such upload")?;
+            ensure!(upload.owner == person, "not your upload");
+            ensure!(index < upload.chunk_count, "chunk index is out of range");
+            ensure!(!bytes.is_empty() && bytes.len() <= Self::max_data_size() as usize, "chunk is too large");
+            let resent = Self::chunk_hash((upload_id, index)).is_some();
+            let old_len = Self::get_chunk((upload_id, index)).len() as u32;
+            let received = upload.received - old_len + bytes.len() as u32;
+            ensure!(received <= upload.total_size, "chunks exceed the announced size");
+
+            let deposit = Self::chunk_deposit(bytes.len() as u32);
+            <balances::Module<T> as ReservableCurrency<_>>::reserve(&person, deposit)?;
+            if let Err(e) = Self::charge_for_bytes(&person, bytes.len()) {
+                <balances::Module<T> as ReservableCurrency<_>>::unreserve(&person, deposit);
+                return Err(e);
+            }
+            let old_deposit = if resent { Self::chunk_deposit(old_len) } else { Zero::zero() };
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&person, old_deposit);
+
+            if !resent {
+                upload.chunks_received += 1;
+            }
+            upload.received = received;
+            upload.deposit = upload.deposit - old_deposit + deposit;
+            upload.expires_at = <system::Module<T>>::block_number() + Self::upload_timeout();
+            <UploadTimeouts<T>>::mutate(upload.expires_at, |ids| if !ids.contains(&upload_id) { ids.push(upload_id) });
+            <ChunkHashes<T>>::insert((upload_id, index), Self::chunk_leaf(&bytes));
+            <Chunks<T>>::insert((upload_id, index), bytes);
+            <ChunkedUploads<T>>::insert(upload_id, upload);
+            Ok(())
+        }
+
+        // Once every chunk arrived and they match the announced root, the chunks become a regular upload.
+        fn finalize_upload(origin, upload_id: u64) -> Result {
+            let person = ensure_signed(origin)?;
+            let upload = Self::chunked_upload(upload_id).ok_or("no such upload")?;
+            ensure!(upload.owner == person, "not your upload");
+            ensure!(upload.chunks_received == upload.chunk_count && upload.received == upload.total_size, "upload is incomplete");
+            let leaves = (0..upload.chunk_count)
+                .map(|index| Self::chunk_hash((upload_id, index)))
+                .collect::<Option<Vec<_>>>().ok_or("upload is incomplete")?;
+            ensure!(Self::merkle_root(&leaves) == upload.root_hash, "chunks do not match the root hash");
+            ensure!(Self::get_metadata(&person, &upload.to_company, upload.order_id).is_none(), "you already uploaded data to this order");
+            // the upload is committed to its root, the chunks are never joined or hashed again on chain
+            let content_hash = upload.root_hash;
+            if Self::order_content(&(upload.to_company.clone(), upload.order_id, content_hash)).is_some() {
+                Self::abort_upload(upload_id, upload);
+                return Err("same data was already uploaded to this order");
+            }
+
+            // the chunks stay where they are, with their hashes and the deposit they already reserved
+            <ChunkedUploads<T>>::remove(upload_id);
+            let hash_key = Self::insert_record(&person, upload.deposit, content_hash, upload.total_size, Some((upload_id, upload.chunk_count)));
+            Self::index_upload(person, upload.data_name, hash_key, content_hash, upload.to_company, upload.order_id);
+            Ok(())
+        }
+
+        // People can give up an unfinished upload and get the deposit of its chunks back.
+        fn cancel_upload(origin, upload_id: u64) -> Result {
+            let person = ensure_signed(origin)?;
+            let upload = Self::chunked_upload(upload_id).ok_or("no such upload")?;
+            ensure!(upload.owner == person, "not your upload");
+            Self::abort_upload(upload_id, upload);
+            Ok(())
+        }
+
+        // People can update their own data when they find somethine changed.
+        // The new content is charged the same way as upload_data().
+        fn update_data(origin, data_name: Vec<u8>, data_content: Vec<u8>, to_company: T::AccountId, order_id: usize) -> Result {
+            let person = ensure_signed(origin)?;
+            ensure!(<People<T>>::exists(&person), "no person");
+            let old = Self::get_metadata(&person, &to_company, order_id).ok_or("no data to update")?;
+            ensure!(data_name.len() <= MAX_NAME_LEN, "data name is too long");
+            ensure!(data_content.len() <= Self::max_data_size() as usize, "data is too large");
+            let content_hash = T::Hashing::hash(&data_content);
+            ensure!(Self::order_content(&(to_company.clone(), order_id, content_hash)).is_none(), "same data was already uploaded to this order");
+            let deposit = Self::pay_for_upload(&person, data_name.len() + data_content.len(), data_content.len())?;
+
+            let hash_key = Self::put_on_chain(&person, data_content, content_hash, deposit);
+            <People<T>>::mutate(&person, |metadata_list| {
+                if let Some(metadata) = metadata_list.iter_mut()
+                    .find(|m| m.to_company == to_company && m.order_id == order_id) {
+                    // sold content is kept until its retention period ends, disputes about it are judged
+                    // against it. Content nobody bought is no longer reachable, give its deposit back.
+                    if !Self::get_data_info(metadata.hash_key).map_or(false, |record| record.purchased) {
+                        Self::remove_from_chain(metadata.hash_key);
+                    }
+                    metadata.hash_key = hash_key;
+                    metadata.data_name = data_name;
+                    metadata.content_hash = content_hash;
+                }
+            });
+            Self::unindex_content(&person, &to_company, order_id, old.content_hash);
+            Self::index_content(&person, &to_company, order_id, content_hash);
+            // the old report no longer describes the data, validate it again.
+            let upload = (person.clone(), to_company.clone(), order_id);
+            Self::add_version(&upload, content_hash);
+            if let Some(mut subscription) = Self::get_subscription(&upload) {
+                subscription.submitted = true;
+                <Subscriptions<T>>::insert(&upload, subscription);
+            }
+            <Reports<T>>::remove(&upload);
+            <FieldCommitments<T>>::remove(&upload);
+            Self::queue_validation(upload);
+            Self::deposit_event(RawEvent::DataUpdated(person, to_company, order_id, content_hash));
+            Ok(())
+        }
+
+        // People can erase their own data at any time and get the storage deposit back.
+        fn erase_data(origin, to_company: T::AccountId, order_id: usize) -> Result {
+            let person = ensure_signed(origin)?;
+            let metadata = Self::get_metadata(&person, &to_company, order_id).ok_or("no data to erase")?;
+            ensure!(Self::get_auction(&(person.clone(), to_company.clone(), order_id)).is_none(), "data is being auctioned");
+            Self::erase_upload(&person, &metadata);
+            Ok(())
+        }
+
+        // Root (e.g. through sudo) changes the marketplace rules.
+        fn set_parameters(origin, parameters: MarketParameters<T::BlockNumber>) -> Result {
+            ensure_root(origin)?;
+            <Parameters<T>>::put(parameters);
+            Self::deposit_event(RawEvent::ParametersChanged);
+            Ok(())
+        }
+
+        // The council (or root) approves a company to publish orders, or revokes it. Its published orders stay.
+        fn set_company_approval(origin, company: T::AccountId, approved: bool) -> Result {
+            T::Governance::ensure_majority(origin)?;
+            if approved {
+                <ApprovedCompanies<T>>::insert(&company, true);
+            } else {
+                <ApprovedCompanies<T>>::remove(&company);
+            }
+            Self::deposit_event(RawEvent::CompanyApproval(company, approved));
+            Ok(())
+        }
+
+        // The technical committee (or root) changes the size limits of orders and uploads.
+        fn set_size_limits(origin, max_data_size: u32, max_order_content_size: u32, max_upload_size: u32) -> Result {
+            T::Governance::ensure_technical(origin)?;
+            <MaxDataSize<T>>::put(max_data_size);
+            <MaxOrderContentSize<T>>::put(max_order_content_size);
+            <MaxUploadSize<T>>::put(max_upload_size);
+            Self::deposit_event(RawEvent::ParametersChanged);
+            Ok(())
+        }
+
+        // Root changes the fees, deposits and bonds. Deposits already reserved are not touched.
+        fn set_fees(origin, byte_fee: T::Balance, deposit_per_byte: T::Balance, min_company_bond: T::Balance, dispute_penalty: T::Balance, appeal_bond: T::Balance, min_bid_deposit: T::Balance) -> Result {
+            ensure_root(origin)?;
+            ensure!(appeal_bond > dispute_penalty, "appeal bond must exceed the dispute penalty");
+            <ByteFee<T>>::put(byte_fee);
+            <DepositPerByte<T>>::put(deposit_per_byte);
+            <MinCompanyBond<T>>::put(min_company_bond);
+            <DisputePenalty<T>>::put(dispute_penalty);
+            <AppealBond<T>>::put(appeal_bond);
+            <MinBidDeposit<T>>::put(min_bid_deposit);
+            Self::deposit_event(RawEvent::ParametersChanged);
+            Ok(())
+        }
+
+        // The technical committee (or root) changes the periods of the marketplace. Only deadlines set afterwards
+        // use the new periods.
+        fn set_periods(origin, abandon_period: T::BlockNumber, retention_period: T::BlockNumber, upload_timeout: T::BlockNumber, delivery_period: T::BlockNumber, evidence_period: T::BlockNumber, dispute_voting_period: T::BlockNumber, appeal_period: T::BlockNumber, appeal_voting_period: T::BlockNumber) -> Result {
+            T::Governance::ensure_technical(origin)?;
+            <DisputeVotingPeriod<T>>::put(dispute_voting_period);
+            <AppealPeriod<T>>::put(appeal_period);
+            <AppealVotingPeriod<T>>::put(appeal_voting_period);
+            <AbandonPeriod<T>>::put(abandon_period);
+            <RetentionPeriod<T>>::put(retention_period);
+            <UploadTimeout<T>>::put(upload_timeout);
+            <DeliveryPeriod<T>>::put(delivery_period);
+            <EvidencePeriod<T>>::put(evidence_period);
+            Self::deposit_event(RawEvent::ParametersChanged);
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+
+    // whether a person holds the claims an order requires and passes its predicate.
+    fn is_eligible(person: &T::AccountId, order: &OrderForm<T::Balance>) -> bool {
+        <attestation::Module<T>>::meets(person, &order.requirements)
+            && <attestation::Module<T>>::evaluate(person, &order.eligibility)
+    }
+
+    // The orders of the given companies a person can upload data for, as (company, order_id).
+    pub fn eligible_orders(person: &T::AccountId, companies: Vec<T::AccountId>) -> Vec<(T::AccountId, usize)> {
+        companies.into_iter()
+            .flat_map(|company| Self::get_order(&company).into_iter()
+                .filter(|order| Self::is_eligible(person, order))
+                .map(move |order| (company.clone(), order.id)))
+            .collect()
+    }
+
+    // A page of all published orders, as (company, order_id), starting at position start.
+    pub fn list_orders(start: u32, count: u32) -> Vec<(T::AccountId, usize)> {
+        Self::page(start, count, Self::order_count(), |position| Self::order_at(position))
+    }
+
+    // A page of the orders published in a category, as (company, order_id).
+    pub fn list_orders_in_category(category: Vec<u8>, start: u32, count: u32) -> Vec<(T::AccountId, usize)> {
+        let total = Self::category_count(&category);
+        Self::page(start, count, total, |position| Self::category_order_at(&(category.clone(), position)))
+    }
+
+    // A page of the orders carrying a tag, as (company, order_id).
+    pub fn list_orders_with_tag(tag: Vec<u8>, start: u32, count: u32) -> Vec<(T::AccountId, usize)> {
+        let total = Self::tag_count(&tag);
+        Self::page(start, count, total, |position| Self::tag_order_at(&(tag.clone(), position)))
+    }
+
+    // read the positions start.. of an index holding total entries, at most MAX_PAGE_SIZE of them.
+    fn page<F>(start: u32, count: u32, total: u32, at: F) -> Vec<(T::AccountId, usize)>
+        where F: Fn(u32) -> Option<(T::AccountId, usize)>
+    {
+        let end = rstd::cmp::min(total, start.saturating_add(rstd::cmp::min(count, MAX_PAGE_SIZE)));
+        (start..end).filter_map(at).collect()
+    }
+
+    // Take what is scheduled for block n, at most budget items. The rest is put in front of what is
+    // scheduled for the next block, so nothing is lost, it is only handled later.
+    fn take_scheduled<K, S>(n: T::BlockNumber, budget: &mut usize) -> Vec<K>
+        where K: Codec, S: StorageMap<T::BlockNumber, Vec<K>, Query = Vec<K>>
+    {
+        let mut due = S::take(&n);
+        if due.len() > *budget {
+            let mut rest = due.split_off(*budget);
+            S::mutate(&(n + One::one()), |next| {
+                rest.extend(next.drain(..));
+                *next = rest;
+            });
+        }
+        *budget -= due.len();
+        due
+    }
+
+    // burn byte_fee for every stored byte, so large payloads cost proportionally more.
+    fn charge_for_bytes(who: &T::AccountId, len: usize) -> Result {
+        let fee = Self::byte_fee() * <T::Balance as As<u64>>::sa(len as u64);
+        <balances::Module<T> as Currency<_>>::withdraw(who, fee, WithdrawReason::TransactionPayment, ExistenceRequirement::KeepAlive)?;
+        Ok(())
+    }
+
+    // the price is put in escrow until the company confirms the data.
+    fn transfer_data(company: T::AccountId, person: T::AccountId, order_id: usize) -> Result{
+        let metadata = Self::get_metadata(&person, &company, order_id).ok_or("no data to buy")?;
+        let order = Self::get_orderform(&company, order_id).ok_or("no orderform")?;
+        Self::ensure_can_sell(&company, &(person.clone(), company.clone(), order_id))?;
+        ensure!(Self::deal_id(&(company.clone(), person.clone(), company.clone(), order_id)).is_none(), "you already bought this data");
+
+        let price = Self::price_of(&company, &person, &order);
+        <balances::Module<T> as ReservableCurrency<_>>::reserve(&company, price)?;
+        Self::open_deal(company, person, &metadata, price);
+        Ok(())
+    }
+
+    // the checks every sale of an upload (person, company, order_id) goes through, whichever way it is bought.
+    fn ensure_can_sell(buyer: &T::AccountId, upload: &(T::AccountId, T::AccountId, usize)) -> Result {
+        ensure!(Self::get_auction(upload).is_none(), "data is being auctioned");
+        // the winner of an exclusive sale can still buy newer versions
+        ensure!(Self::exclusive_to(upload).map_or(true, |winner| &winner == buyer), "data was sold exclusively");
+        ensure!(Self::company_bond(buyer) >= Self::min_company_bond(), "bond is too small");
+        Ok(())
+    }
+
+    // whether a company holds the bond and the balance to buy a person's upload to an order.
+    fn can_buy(company: &T::AccountId, person: &T::AccountId, order_id: usize) -> bool {
+        let price = Self::current_price(company, person, order_id).unwrap_or_default();
+        Self::company_bond(company) >= Self::min_company_bond()
+            && <balances::Module<T> as ReservableCurrency<_>>::can_reserve(company, price)
+    }
+
+    // What buying a person's upload to an order costs right now.
+    pub fn current_price(company: &T::AccountId, person: &T::AccountId, order_id: usize) -> Option<T::Balance> {
+        Self::get_orderform(company, order_id).map(|order| Self::price_of(company, person, &order))
+    }
+
+    // The price of an upload only depends on when it was submitted, not on when or in which order it is bought.
+    fn price_of(company: &T::AccountId, person: &T::AccountId, order: &OrderForm<T::Balance>) -> T::Balance {
+        let key = (company.clone(), order.id);
+        let (pricing, since) = match Self::order_pricing(&key) {
+            Some(pricing) => pricing,
+            None => return order.unit_price,
+        };
+        let submissions = Self::submission_index(&(person.clone(), company.clone(), order.id));
+        match pricing {
+            Pricing::Flat => order.unit_price,
+            Pricing::LinearDecrease { step, floor } => {
+                let discount = step.saturating_mul(<T::Balance as As<u64>>::sa(submissions as u64));
+                rstd::cmp::max(order.unit_price.saturating_sub(discount), floor)
+            },
+            Pricing::Tiers(tiers) => tiers.iter()
+                .find(|(until, _)| submissions < *until)
+                .or_else(|| tiers.last())
+                .map(|(_, price)| *price)
+                .unwrap_or(order.unit_price),
+            Pricing::Decay { interval, step, floor } => {
+                let now = <system::Module<T>>::block_number();
+                let intervals = (now.saturating_sub(since) / interval).as_();
+                let discount = step.saturating_mul(<T::Balance as As<u64>>::sa(intervals));
+                rstd::cmp::max(order.unit_price.saturating_sub(discount), floor)
+            },
+        }
+    }
+
+    // record a deal whose price is already reserved on the buyer's account.
+    fn open_deal(buyer: T::AccountId, person: T::AccountId, metadata: &DataMetadata<T::AccountId, T::Hash>, price: T::Balance) {
+        let order_id = metadata.order_id;
+        let now = <system::Module<T>>::block_number();
+        let version = Self::versions(&(person.clone(), metadata.to_company.clone(), order_id)).len().saturating_sub(1) as u32;
+        // auction winners bought outside of the order, they always pay what they bid
+        let rating_table = match buyer == metadata.to_company {
+            true => Self::rating_table(&(buyer.clone(), order_id)).unwrap_or_default(),
+            false => Vec::new(),
+        };
+        let deal_id = Self::deal_count();
+        <DealCount<T>>::put(deal_id + 1);
+        <DealIds<T>>::insert(&(buyer.clone(), person.clone(), metadata.to_company.clone(), order_id), deal_id);
+        <Deals<T>>::insert(deal_id, Deal {
+            buyer: buyer.clone(),
+            person: person.clone(),
+            to_company: metadata.to_company.clone(),
+            order_id: order_id,
+            price: price,
+            content_hash: metadata.content_hash,
+            hash_key: metadata.hash_key,
+            version: version,
+            status: DealStatus::Paid,
+            purchased_at: now,
+            receipt: None,
+            rating: 0,
+            rating_table: rating_table,
+        });
+        Self::mark_purchased(metadata.hash_key);
+        <PaidDeals<T>>::mutate(&buyer, |n| *n += 1);
+        T::Currency::set_lock(PERSON_LOCK, &person, Bounded::max_value(), now + Self::parameters().person_lock_period, WithdrawReasons::all());
+        Self::deposit_event(RawEvent::DataPurchased(buyer, person, order_id, deal_id, price, metadata.content_hash));
+    }
+
+    // pay a subscriber for the period which ends now, or count the period as missed.
+    fn close_period(key: (T::AccountId, T::AccountId, usize), now: T::BlockNumber) {
+        let mut subscription = match Self::get_subscription(&key) {
+            Some(ref subscription) if subscription.period_end <= now => subscription.clone(),
+            _ => return,
+        };
+        let (person, company, order_id) = key.clone();
+        let terms = match Self::subscription_terms(&(company.clone(), order_id)) {
+            Some(terms) => terms,
+            None => return Self::end_subscription(key),
+        };
+
+        if subscription.submitted {
+            let allowance = Self::allowance(&(company.clone(), order_id));
+            if allowance < terms.price
+                || <balances::Module<T> as ReservableCurrency<_>>::repatriate_reserved(&company, &person, terms.price).is_err() {
+                // the company ran out of allowance, nobody is charged for a period it can't pay.
+                return Self::end_subscription(key);
+            }
+            <Allowances<T>>::insert(&(company.clone(), order_id), allowance - terms.price);
+            subscription.paid_periods += 1;
+            Self::deposit_event(RawEvent::SubscriptionCharged(company, person, order_id, terms.price));
+        } else {
+            subscription.missed_periods += 1;
+            Self::deposit_event(RawEvent::PeriodMissed(person, company, order_id, subscription.missed_periods));
+        }
+
+        subscription.submitted = false;
+        subscription.period_end = now + terms.period;
+        <PeriodEnds<T>>::mutate(subscription.period_end, |ends| ends.push(key.clone()));
+        <Subscriptions<T>>::insert(&key, subscription);
+    }
+
+    fn end_subscription(key: (T::AccountId, T::AccountId, usize)) {
+        <Subscriptions<T>>::remove(&key);
+        let (person, company, order_id) = key;
+        Self::deposit_event(RawEvent::SubscriptionCancelled(person, company, order_id));
+    }
+
+    // hand the upload to the highest bidder, forfeit the bonds of bids which were never revealed.
+    fn settle_auction(upload: (T::AccountId, T::AccountId, usize)) {
+        let auction = match <Auctions<T>>::take(&upload) {
+            Some(auction) => auction,
+            None => return,
+        };
+        let (person, to_company, order_id) = upload.clone();
+        for bidder in <Bidders<T>>::take(&upload) {
+            <Commitments<T>>::remove(&(person.clone(), to_company.clone(), order_id, bidder.clone()));
+            Self::pay_reserved(&bidder, &person, auction.bond);
+        }
+
+        if let Some((winner, amount)) = auction.best_bid {
+            match Self::get_metadata(&person, &to_company, order_id) {
+                Some(ref metadata) if Self::deal_id(&(winner.clone(), person.clone(), to_company.clone(), order_id)).is_none() => {
+                    <ExclusiveTo<T>>::insert(&upload, winner.clone());
+                    Self::open_deal(winner.clone(), person.clone(), metadata, amount);
+                    Self::deposit_event(RawEvent::AuctionWon(person, to_company, order_id, winner, amount));
+                    return;
+                },
+                _ => {
+                    <balances::Module<T> as ReservableCurrency<_>>::unreserve(&winner, amount);
+                },
+            }
+        }
+        Self::deposit_event(RawEvent::AuctionFailed(person, to_company, order_id));
+    }
+
+    // what storing len bytes on the chain reserves, deposit_per_byte for every byte until the data is erased.
+    fn data_deposit(len: usize) -> T::Balance {
+        Self::deposit_per_byte() * <T::Balance as As<u64>>::sa(len as u64)
+    }
+
+    // Reserve the deposit of content_len stored bytes and charge byte_fee for fee_len bytes, returns the deposit.
+    // Either both are taken or neither, so callers can store the upload once this succeeded.
+    fn pay_for_upload(who: &T::AccountId, fee_len: usize, content_len: usize) -> rstd::result::Result<T::Balance, &'static str> {
+        let deposit = Self::data_deposit(content_len);
+        <balances::Module<T> as ReservableCurrency<_>>::reserve(who, deposit)?;
+        if let Err(e) = Self::charge_for_bytes(who, fee_len) {
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(who, deposit);
+            return Err(e);
+        }
+        Ok(deposit)
+    }
+
+    // store data whose deposit is already taken care of, content_hash is its hash.
+    fn put_on_chain(owner: &T::AccountId, value: Vec<u8>, content_hash: T::Hash, deposit: T::Balance) -> u64 {
+        let hash_key = Self::insert_record(owner, deposit, content_hash, value.len() as u32, None);
+        <Data<T>>::insert(&hash_key, value);
+        hash_key
+    }
+
+    // record a Data entry, its content is in Data or in the chunks of a chunked upload.
+    fn insert_record(owner: &T::AccountId, deposit: T::Balance, content_hash: T::Hash, size: u32, chunks: Option<(u64, u32)>) -> u64 {
+        let hash_key= Self::get_n();
+        let expires_at = <system::Module<T>>::block_number() + Self::abandon_period();
+        <DataInfo<T>>::insert(&hash_key, DataRecord {
+            owner: owner.clone(),
+            deposit: deposit,
+            content_hash: content_hash,
+            size: size,
+            chunks: chunks,
+            purchased: false,
+            expires_at: expires_at,
+        });
+        <Expiring<T>>::mutate(expires_at, |keys| keys.push(hash_key));
+        <Nonce<T>>::mutate(|n| *n += 1);
+        hash_key
+    }
+
+    // erase data from the chain and unreserve its deposit.
+    fn remove_from_chain(key: u64) {
+        <Data<T>>::remove(&key);
+        if let Some(record) = <DataInfo<T>>::take(&key) {
+            if let Some((upload_id, chunk_count)) = record.chunks {
+                for index in 0..chunk_count {
+                    <Chunks<T>>::remove((upload_id, index));
+                    <ChunkHashes<T>>::remove((upload_id, index));
+                }
+            }
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&record.owner, record.deposit);
+        }
+    }
+
+    // store a new upload and index it, the caller has checked it and reserved its deposit.
+    fn insert_upload(person: T::AccountId, data_name: Vec<u8>, data_content: Vec<u8>, deposit: T::Balance, to_company: T::AccountId, order_id: usize) {
+        let content_hash = T::Hashing::hash(&data_content);
+        let hash_key = Self::put_on_chain(&person, data_content, content_hash, deposit);
+        Self::index_upload(person, data_name, hash_key, content_hash, to_company, order_id);
+    }
+
+    fn index_upload(person: T::AccountId, data_name: Vec<u8>, hash_key: u64, content_hash: T::Hash, to_company: T::AccountId, order_id: usize) {
+        let new_data: DataMetadata<T::AccountId, T::Hash> = DataMetadata{
+            hash_key: hash_key,
+            data_name: data_name,
+            to_company: to_company.clone(),
+            order_id: order_id,
+            content_hash: content_hash,
+        };
+        <People<T>>::mutate(&person, |metadata_list| metadata_list.push(new_data));
+        Self::index_content(&person, &to_company, order_id, content_hash);
+        Self::add_version(&(person.clone(), to_company.clone(), order_id), content_hash);
+        let key = (to_company.clone(), order_id);
+        let position = Self::uploader_count(&key);
+        <UploaderAt<T>>::insert(&(to_company.clone(), order_id, position), person.clone());
+        <UploaderCount<T>>::insert(&key, position + 1);
+        <UploaderPosition<T>>::insert(&(person.clone(), to_company.clone(), order_id), position);
+        let submissions = Self::submissions(&(to_company.clone(), order_id));
+        <SubmissionIndex<T>>::insert(&(person.clone(), to_company.clone(), order_id), submissions);
+        <Submissions<T>>::insert(&(to_company.clone(), order_id), submissions + 1);
+        Self::queue_validation((person.clone(), to_company.clone(), order_id));
+        Self::deposit_event(RawEvent::DataUploaded(person, to_company, order_id, content_hash));
+    }
+
+    // append an order to the company's list and index it, returns its id.
+    fn insert_order(company: T::AccountId, order_name: Vec<u8>, content: Vec<u8>, unit_price: T::Balance, requirements: Vec<Claim>, eligibility: Vec<PredicateOp>, category: Vec<u8>, tags: Vec<Vec<u8>>) -> usize {
+        let mut order_id = 0;
+        <Company<T>>::mutate(&company, |order_list| {
+            order_id = order_list.len();
+            let new_order: OrderForm<T::Balance> = OrderForm{
+                id: order_id,
+                order_name: order_name,
+                content: content,
+                unit_price: unit_price,
+                requirements: requirements,
+                eligibility: eligibility,
+                category: category.clone(),
+                tags: tags.clone(),
+            };
+            order_list.push(new_order);
+        });
+        let order = (company.clone(), order_id);
+        let position = Self::order_count();
+        <OrderIndex<T>>::insert(position, order.clone());
+        <OrderCount<T>>::put(position + 1);
+        let position = Self::category_count(&category);
+        <CategoryOrders<T>>::insert(&(category.clone(), position), order.clone());
+        <CategoryCount<T>>::insert(&category, position + 1);
+        for (i, tag) in tags.iter().enumerate() {
+            // a tag given twice is indexed once
+            if tags[..i].contains(tag) {
+                continue;
+            }
+            let position = Self::tag_count(tag);
+            <TagOrders<T>>::insert(&(tag.clone(), position), order.clone());
+            <TagCount<T>>::insert(tag, position + 1);
+        }
+        Self::deposit_event(RawEvent::OrderPublished(company, order_id, unit_price));
+        order_id
+    }
+
+    // Merkle leaf of the field at index, the index is part of the leaf so a proof can't move a value around.
+    pub fn field_leaf(index: u32, value: &[u8]) -> T::Hash {
+        T::Hashing::hash_of(&(MERKLE_LEAF, index, value))
+    }
+
+    // Check the inclusion proof of a field against the root of a tree built by merkle_root().
+    pub fn verify_field(root: T::Hash, index: u32, value: &[u8], proof: &[T::Hash]) -> bool {
+        let mut position = index;
+        let mut node = Self::field_leaf(index, value);
+        for sibling in proof {
+            node = if position % 2 == 0 {
+                Self::merkle_node(node, *sibling)
+            } else {
+                Self::merkle_node(*sibling, node)
+            };
+            position /= 2;
+        }
+        node == root
+    }
+
+    // hold a deal in escrow until the evidence window of its dispute closes.
+    fn open_dispute(deal_id: u64, opened_by: T::AccountId, reason: Option<DisputeReason>) {
+        <Deals<T>>::mutate(deal_id, |deal| if let Some(deal) = deal { deal.status = DealStatus::Disputed });
+        let now = <system::Module<T>>::block_number();
+        let evidence_end = now + Self::evidence_period();
+        <Disputes<T>>::insert(deal_id, Dispute {
+            opened_by: opened_by,
+            reason: reason,
+            opened_at: now,
+            evidence_end: evidence_end,
+            evidence: Vec::new(),
+            council_end: None,
+            verdict: None,
+        });
+        <DisputeEnds<T>>::mutate(evidence_end, |deal_ids| deal_ids.push(deal_id));
+        // keep the content the dispute is about around until it is judged
+        if let Some(deal) = Self::get_deal(deal_id) {
+            Self::mark_purchased(deal.hash_key);
+        }
+        Self::deposit_event(RawEvent::DisputeOpened(deal_id));
+    }
+
+    // Called when the evidence window of a dispute closes, and again when the council's time is over.
+    // A dispute with evidence is referred to the council first, which has dispute_voting_period blocks
+    // to decide it with decide_dispute(). Otherwise the automatic check judges it.
+    fn resolve_dispute(deal_id: u64) {
+        let mut dispute = match Self::get_dispute(deal_id) {
+            Some(dispute) => dispute,
+            None => return,
+        };
+        if Self::get_deal(deal_id).map(|deal| deal.status) != Some(DealStatus::Disputed) {
+            return;
+        }
+        let now = <system::Module<T>>::block_number();
+        match dispute.council_end {
+            // still the council's turn
+            Some(council_end) if now < council_end => return,
+            _ => {},
+        }
+        if !dispute.evidence.is_empty() && dispute.council_end.is_none() {
+            let council_end = now + Self::dispute_voting_period();
+            dispute.council_end = Some(council_end);
+            <Disputes<T>>::insert(deal_id, dispute);
+            <DisputeEnds<T>>::mutate(council_end, |deal_ids| deal_ids.push(deal_id));
+            Self::deposit_event(RawEvent::DisputeReferred(deal_id));
+            return;
+        }
+        Self::settle_dispute(deal_id, None);
+    }
+
+    // Judge a disputed deal by the council's decision (whether the data is legal), or by checking the data
+    // against its order.
+    // If it is legal, the company does evil: the person gets paid, plus dispute_penalty from the company's bond,
+    // and we lock the company's account for punishment.
+    // Otherwise the person does evil: the company gets its money back and we keep locking the person's account.
+    // Everything is looked up before any balance moves and nothing can fail afterwards, the payments are
+    // capped at what the company has reserved.
+    fn settle_dispute(deal_id: u64, decision: Option<bool>) {
+        let mut deal = match Self::get_deal(deal_id) {
+            Some(deal) if deal.status == DealStatus::Disputed && <Disputes<T>>::exists(deal_id) => deal,
+            _ => return,
+        };
+        let (company, person) = (deal.buyer.clone(), deal.person.clone());
+        // a withdrawn order is judged by the marketplace rules alone
+        let order_content = Self::get_orderform(&deal.to_company, deal.order_id)
+            .map(|order| order.content)
+            .unwrap_or_default();
+        // judge the content the buyer paid for by the hash it was committed to, erased data can't be legal
+        let record = Self::get_data_info(deal.hash_key).filter(|record| record.content_hash == deal.content_hash);
+
+        let is_legal = decision.unwrap_or_else(||
+            record.map_or(false, |record| Self::validate_record(deal.hash_key, &record, order_content)));
+        if is_legal {
+            Self::pay_reserved(&company, &person, deal.price);
+            let bond = Self::company_bond(&company);
+            let penalty = Self::pay_reserved(&company, &person, rstd::cmp::min(bond, Self::dispute_penalty()));
+            <CompanyBond<T>>::insert(&company, bond - penalty);
+            <PenaltiesPaid<T>>::insert(deal_id, penalty);
+            deal.status = DealStatus::Confirmed;
+            let until = <system::Module<T>>::block_number() + Self::parameters().company_lock_period;
+            T::Currency::set_lock(COMPANY_LOCK, &company, Bounded::max_value(), until, Self::company_lock_reasons());
+            T::Currency::remove_lock(PERSON_LOCK, &person);
+        } else {
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&company, deal.price);
+            deal.status = DealStatus::Refunded;
+        }
+        <Deals<T>>::insert(deal_id, deal);
+        <PaidDeals<T>>::mutate(&company, |n| *n = n.saturating_sub(1));
+
+        // the bond can pay for an overturned verdict until it can't be appealed anymore
+        let now = <system::Module<T>>::block_number();
+        let appealable_until = now + Self::appeal_period();
+        if appealable_until > Self::appealable_until(&company) {
+            <AppealableUntil<T>>::insert(&company, appealable_until);
+        }
+        let winner = if is_legal { person } else { company };
+        <Disputes<T>>::mutate(deal_id, |dispute| if let Some(dispute) = dispute {
+            dispute.verdict = Some((winner.clone(), now));
+        });
+        Self::deposit_event(RawEvent::DisputeResolved(deal_id, winner));
+    }
+
+    // Close an appeal the council decided, or upheld by not deciding it in time. An overturned verdict moves
+    // what the winner got back to the appellant as far as the winner's free balance allows.
+    fn close_appeal(deal_id: u64, overturned: bool) {
+        let (mut appeal, mut deal) = match (Self::get_appeal(deal_id), Self::get_deal(deal_id)) {
+            (Some(appeal), Some(deal)) if appeal.overturned.is_none() => (appeal, deal),
+            _ => return,
+        };
+        let (company, person) = (deal.buyer.clone(), deal.person.clone());
+
+        let winner = if appeal.appellant == company { person.clone() } else { company.clone() };
+        if overturned {
+            <balances::Module<T> as ReservableCurrency<_>>::unreserve(&appeal.appellant, appeal.bond);
+            let now = <system::Module<T>>::block_number();
+            if winner == person {
+                // the company gets the price back, and the penalty back into its bond
+                let penalty = <PenaltiesPaid<T>>::take(deal_id);
+                Self::clawback(&person, &company, deal.price);
+                let penalty = Self::clawback(&person, &company, penalty);
+                if <balances::Module<T> as ReservableCurrency<_>>::reserve(&company, penalty).is_ok() {
+                    <CompanyBond<T>>::mutate(&company, |bond| *bond = *bond + penalty);
+                }
+                deal.status = DealStatus::Refunded;
+                T::Currency::remove_lock(COMPANY_LOCK, &company);
+            } else {
+                // the person gets the price, and the penalty from the company's bond
+                Self::clawback(&company, &person, deal.price);
+                let bond = Self::company_bond(&company);
+                let penalty = Self::pay_reserved(&company, &person, rstd::cmp::min(bond, Self::dispute_penalty()));
+                <CompanyBond<T>>::insert(&company, bond - penalty);
+                <PenaltiesPaid<T>>::insert(deal_id, penalty);
+                deal.status = DealStatus::Confirmed;
+                let until = now + Self::parameters().company_lock_period;
+                T::Currency::set_lock(COMPANY_LOCK, &company, Bounded::max_value(), until, Self::company_lock_reasons());
+                T::Currency::remove_lock(PERSON_LOCK, &person);
+            }
+            <Deals<T>>::insert(deal_id, deal);
+            <Disputes<T>>::mutate(deal_id, |dispute| if let Some(dispute) = dispute {
+                dispute.verdict = Some((appeal.appellant.clone(), now));
+            });
+        } else {
+            Self::pay_reserved(&appeal.appellant, &winner, appeal.bond);
+        }
+        <OpenAppeals<T>>::mutate(&company, |n| *n = n.saturating_sub(1));
+        appeal.overturned = Some(overturned);
+        <Appeals<T>>::insert(deal_id, appeal);
+        Self::deposit_event(RawEvent::AppealDecided(deal_id, overturned));
+    }
+
+    // A company which lost a dispute can't move its money away, but can still pay fees and reserve an appeal bond.
+    fn company_lock_reasons() -> WithdrawReasons {
+        WithdrawReasons::from(WithdrawReason::Transfer)
+    }
+
+    // move up to amount of what from has reserved to the free balance of to, returns what was moved.
+    fn pay_reserved(from: &T::AccountId, to: &T::AccountId, amount: T::Balance) -> T::Balance {
+        let amount = rstd::cmp::min(amount, <balances::Module<T> as ReservableCurrency<_>>::reserved_balance(from));
+        match <balances::Module<T> as ReservableCurrency<_>>::repatriate_reserved(from, to, amount) {
+            Ok(()) => amount,
+            Err(_) => Zero::zero(),
+        }
+    }
+
+    // move up to amount from the free balance of from to to, returns what was moved.
+    fn clawback(from: &T::AccountId, to: &T::AccountId, amount: T::Balance) -> T::Balance {
+        let amount = rstd::cmp::min(amount, <balances::Module<T> as Currency<_>>::free_balance(from));
+        if <balances::Module<T> as ReservableCurrency<_>>::reserve(from, amount).is_err() {
+            return Zero::zero();
+        }
+        match <balances::Module<T> as ReservableCurrency<_>>::repatriate_reserved(from, to, amount) {
+            Ok(()) => amount,
+            Err(_) => Zero::zero(),
+        }
+    }
+
+    // store the receipt of a delivery on its deal, the content has to be the one the deal is bound to.
+    fn record_receipt(deal_id: u64, mut deal: Deal<T::AccountId, T::Balance, T::Hash, T::BlockNumber>, content_hash: T::Hash) -> Result {
+        ensure!(deal.receipt.is_none(), "delivery was already acknowledged");
+        ensure!(deal.content_hash == content_hash, "receipt is for another version of the data");
+        deal.receipt = Some((content_hash, <system::Module<T>>::block_number()));
+        <Deals<T>>::insert(deal_id, deal);
+        Self::deposit_event(RawEvent::DeliveryAcknowledged(deal_id, content_hash));
+        Ok(())
+    }
+
+    fn add_version(upload: &(T::AccountId, T::AccountId, usize), content_hash: T::Hash) {
+        <Versions<T>>::mutate(upload, |versions| versions.push(DataVersion {
+            content_hash: content_hash,
+            uploaded_at: <system::Module<T>>::block_number(),
+        }));
+    }
+
+    // drop an unfinished chunked upload and unreserve the deposit of its chunks.
+    fn abort_upload(upload_id: u64, upload: ChunkedUpload<T::AccountId, T::Balance, T::Hash, T::BlockNumber>) {
+        for index in 0..upload.chunk_count {
+            <Chunks<T>>::remove((upload_id, index));
+            <ChunkHashes<T>>::remove((upload_id, index));
+        }
+        <ChunkedUploads<T>>::remove(upload_id);
+        <balances::Module<T> as ReservableCurrency<_>>::unreserve(&upload.owner, upload.deposit);
+        Self::deposit_event(RawEvent::UploadAborted(upload.owner, upload_id));
+    }
+
+    // what a chunk of the given length reserves
+    fn chunk_deposit(len: u32) -> T::Balance {
+        Self::deposit_per_byte() * <T::Balance as As<u64>>::sa(len as u64 + CHUNK_DEPOSIT_BYTES)
+    }
+
+    // Merkle leaf of a chunk of a chunked upload.
+    pub fn chunk_leaf(bytes: &[u8]) -> T::Hash {
+        T::Hashing::hash_of(&(MERKLE_LEAF, bytes))
+    }
+
+    // inner node of a merkle tree
+    fn merkle_node(left: T::Hash, right: T::Hash) -> T::Hash {
+        T::Hashing::hash_of(&(MERKLE_NODE, left, right))
+    }
+
+    // Root of a binary merkle tree over the given leaves, an odd node is paired with itself.
+    pub fn merkle_root(leaves: &[T::Hash]) -> T::Hash {
+        if leaves.is_empty() {
+            return Default::default();
+        }
+        let mut level = leaves.to_vec();
+        while level.len() > 1 {
+            level = level.chunks(2)
+                .map(|pair| Self::merkle_node(pair[0], pair[pair.len() - 1]))
+                .collect();
+        }
+        level[0]
+    }
+
+    // drop an upload together with everything indexed by it.
+    fn erase_upload(person: &T::AccountId, metadata: &DataMetadata<T::AccountId, T::Hash>) {
+        let company = metadata.to_company.clone();
+        let order_id = metadata.order_id;
+        let upload = (person.clone(), company.clone(), order_id);
+
+        <People<T>>::mutate(person, |metadata_list| metadata_list.retain(|m| m.hash_key != metadata.hash_key));
+        Self::remove_from_chain(metadata.hash_key);
+        if let Some(position) = <UploaderPosition<T>>::take(&upload) {
+            <UploaderAt<T>>::remove(&(company.clone(), order_id, position));
+        }
+        Self::dequeue_validation(&upload);
+        <Reports<T>>::remove(&upload);
+        <SubmissionIndex<T>>::remove(&upload);
+        <FieldCommitments<T>>::remove(&upload);
+        <Versions<T>>::remove(&upload);
+        // the deals stay open, but an upload made again to the order is a new one and can be sold again
+        if let Some(winner) = <ExclusiveTo<T>>::take(&upload) {
+            <DealIds<T>>::remove(&(winner, person.clone(), company.clone(), order_id));
+        }
+        <DealIds<T>>::remove(&(company.clone(), person.clone(), company.clone(), order_id));
+        if Self::get_subscription(&upload).is_some() {
+            Self::end_subscription(upload.clone());
+        }
+        Self::unindex_content(person, &company, order_id, metadata.content_hash);
+        Self::deposit_event(RawEvent::DataErased(person.clone(), company, order_id));
+    }
+
+    // remember which order received a content, and flag it if the company asked for it.
+    fn index_content(person: &T::AccountId, company: &T::AccountId, order_id: usize, content_hash: T::Hash) {
+        <OrderContent<T>>::insert(&(company.clone(), order_id, content_hash), person.clone());
+        let other_orders = <CompanyContent<T>>::get(&(company.clone(), content_hash));
+        if Self::cross_order_check(&(company.clone(), order_id)) {
+            if let Some(&other_order) = other_orders.iter().find(|&&id| id != order_id) {
+                <Flags<T>>::mutate(&(company.clone(), order_id), |flags| flags.push((person.clone(), other_order)));
+                Self::deposit_event(RawEvent::DataFlagged(person.clone(), company.clone(), order_id, other_order));
+            }
+        }
+        <CompanyContent<T>>::mutate(&(company.clone(), content_hash), |orders| orders.push(order_id));
+    }
+
+    fn unindex_content(person: &T::AccountId, company: &T::AccountId, order_id: usize, content_hash: T::Hash) {
+        <OrderContent<T>>::remove(&(company.clone(), order_id, content_hash));
+        <CompanyContent<T>>::mutate(&(company.clone(), content_hash), |orders| {
+            if let Some(pos) = orders.iter().position(|&id| id == order_id) {
+                orders.remove(pos);
+            }
+        });
+        <Flags<T>>::mutate(&(company.clone(), order_id), |flags| flags.retain(|(p, _)| p != person));
+    }
+
+    // Give up an aggregate order nobody published a result for: unreserve its budget and deposit and drop its
+    // answers. The order can be opened as an aggregate order again.
+    fn expire_aggregate(key: (T::AccountId, usize)) {
+        if !Self::pending_aggregates().contains(&key) {
+            return;
+        }
+        let aggregate = match Self::aggregate_order(&key) {
+            Some(aggregate) => aggregate,
+            None => return,
+        };
+        let (company, order_id) = key.clone();
+        <PendingAggregates<T>>::mutate(|pending| pending.retain(|k| k != &key));
+        for index in 0..<AnswerCount<T>>::take(&key) {
+            if let Some((person, _)) = <Answers<T>>::take(&(company.clone(), order_id, index)) {
+                <AnswerIndex<T>>::remove(&(company.clone(), order_id, person));
+            }
+        }
+        <AggregateOrders<T>>::remove(&key);
+        <balances::Module<T> as ReservableCurrency<_>>::unreserve(&company, aggregate.budget + aggregate.deposit);
+        Self::deposit_event(RawEvent::AggregateExpired(company, order_id));
+    }
+
+    // The encrypted answers of the orders whose answer window closed, for the reporters to decrypt and aggregate.
+    pub fn aggregate_inputs() -> Vec<AggregateInput<T::AccountId>> {
+        let now = <system::Module<T>>::block_number();
+        Self::pending_aggregates().into_iter()
+            .filter_map(|(company, order_id)| {
+                let key = (company.clone(), order_id);
+                let aggregate = Self::aggregate_order(&key).filter(|aggregate| now >= aggregate.answer_end)?;
+                let answers = (0..Self::answer_count(&key))
+                    .map(|index| Self::answer(&(company.clone(), order_id, index)).unwrap_or_default())
+                    .collect();
+                Some(AggregateInput {
+                    company: company,
+                    order_id: order_id,
+                    encryption_key: aggregate.encryption_key,
+                    max_value: aggregate.max_value,
+                    buckets: aggregate.buckets,
+                    epsilon_millis: aggregate.epsilon_millis,
+                    answers: answers,
+                })
+            })
+            .collect()
+    }
+
+    // The checks of the uploads waiting for validation, oldest first, for the reporters to sign and submit.
+    pub fn pending_checks(max: u32) -> Vec<UploadCheck<T::AccountId, T::Hash>> {
+        let (first, next) = Self::validation_queue_bounds();
+        (first..next)
+            .filter_map(|position| Self::validation_queue(position))
+            .filter_map(|(person, company, order_id)| Self::validate_upload(&person, &company, order_id))
+            .take(rstd::cmp::min(max as usize, VALIDATIONS_PER_BLOCK))
+            .collect()
+    }
+
+    // Pool validation of the unsigned extrinsics of this module, the runtime calls it because its Executive
+    // refuses unsigned extrinsics. Only reports signed by a reporter for something still waiting for one pass.
+    pub fn validate_unsigned(call: &Call<T>) -> TransactionValidity {
+        let provides = match call {
+            Call::report_validation(check, reporter, signature) => {
+                let upload = (check.person.clone(), check.company.clone(), check.order_id);
+                if !Self::is_pending(&upload) || Self::check_reporter(&check.payload(), reporter, signature).is_err() {
+                    return TransactionValidity::Invalid(0);
+                }
+                (VALIDATION_CONTEXT, upload).encode()
+            },
+            Call::report_aggregate(report, reporter, signature) => {
+                let key = (report.company.clone(), report.order_id);
+                let owner = Self::aggregate_order(&key).and_then(|aggregate| Self::aggregation_key_owner(&aggregate.encryption_key));
+                if !Self::pending_aggregates().contains(&key) || owner.as_ref() != Some(reporter)
+                    || Self::check_reporter(&report.payload(), reporter, signature).is_err() {
+                    return TransactionValidity::Invalid(0);
+                }
+                (AGGREGATE_CONTEXT, key).encode()
+            },
+            Call::register_aggregation_key(key, reporter, signature) => {
+                if Self::aggregation_key_owner(key).is_some()
+                    || Self::check_reporter(&aggregation_key_payload(key), reporter, signature).is_err() {
+                    return TransactionValidity::Invalid(0);
+                }
+                (AGGREGATION_KEY_CONTEXT, key).encode()
+            },
+            _ => return TransactionValidity::Invalid(0),
+        };
+        TransactionValidity::Valid {
+            priority: 0,
+            requires: Vec::new(),
+            provides: vec![provides],
+            longevity: REPORT_LONGEVITY,
+        }
+    }
+
+    // whether a report was signed by a key the runtime trusts to report.
+    fn check_reporter(payload: &[u8], reporter: &ReporterId<T>, signature: &ReporterSignature<T>) -> Result {
+        ensure!(T::Reporters::is_reporter(reporter), "not a reporter");
+        ensure!(signature.verify(payload, reporter), "invalid report signature");
+        Ok(())
+    }
+
+    fn is_pending(upload: &(T::AccountId, T::AccountId, usize)) -> bool {
+        Self::pending_position(upload).is_some()
+    }
+
+    // queue an upload for validation unless it is waiting already. A full queue drops its oldest position.
+    fn queue_validation(upload: (T::AccountId, T::AccountId, usize)) {
+        if Self::is_pending(&upload) {
+            return;
+        }
+        let (mut first, next) = Self::validation_queue_bounds();
+        if next - first >= MAX_PENDING_VALIDATIONS {
+            if let Some(oldest) = <ValidationQueue<T>>::take(first) {
+                <PendingValidation<T>>::remove(&oldest);
+            }
+            first += 1;
+        }
+        <ValidationQueue<T>>::insert(next, upload.clone());
+        <PendingValidation<T>>::insert(&upload, next);
+        <ValidationQueueBounds<T>>::put((first, next + 1));
+    }
+
+    fn dequeue_validation(upload: &(T::AccountId, T::AccountId, usize)) {
+        if let Some(position) = <PendingValidation<T>>::take(upload) {
+            <ValidationQueue<T>>::remove(position);
+        }
+    }
+
+    // Off-chain checks of one upload.
+    // Reading every other upload of the order is too expensive to do inside a block, that's why this runs off-chain.
+    fn validate_upload(person: &T::AccountId, company: &T::AccountId, order_id: usize) -> Option<UploadCheck<T::AccountId, T::Hash>> {
+        let metadata = Self::get_metadata(person, company, order_id)?;
+        let order = Self::get_orderform(company, order_id);
+        let data = Self::get_from_chain(metadata.hash_key);
+
+        let size_ok = !data.is_empty() && data.len() <= rstd::cmp::max(Self::max_data_size(), Self::max_upload_size()) as usize;
+        let schema_ok = match order {
+            Some(order) => Self::validate_data(data, order.content),
+            None => false,
+        };
+        let duplicate = (0..Self::uploader_count(&(company.clone(), order_id)))
+            .filter_map(|position| Self::uploader_at(&(company.clone(), order_id, position)))
+            .filter(|other| other != person)
+            .filter_map(|other| Self::get_metadata(&other, company, order_id))
+            .any(|other| other.content_hash == metadata.content_hash);
+
+        Some(UploadCheck {
+            person: person.clone(),
+            company: company.clone(),
+            order_id: order_id,
+            content_hash: metadata.content_hash,
+            size_ok: size_ok,
+            schema_ok: schema_ok,
+            duplicate: duplicate,
+        })
+    }
+
+    // purchased data is no longer abandoned, keep it for retention_period from now on.
+    fn mark_purchased(key: u64) {
+        if let Some(mut record) = Self::get_data_info(key) {
+            let expires_at = <system::Module<T>>::block_number() + Self::retention_period();
+            record.purchased = true;
+            record.expires_at = expires_at;
+            <DataInfo<T>>::insert(&key, record);
+            <Expiring<T>>::mutate(expires_at, |keys| keys.push(key));
+        }
+    }
+
+    // the content of a Data entry, the chunks of a chunked upload are joined. Too expensive for large content
+    // inside a block, it is read off-chain.
+    fn get_from_chain(key: u64) -> Vec<u8> {
+        match Self::get_data_info(key).and_then(|record| record.chunks) {
+            Some((upload_id, chunk_count)) => (0..chunk_count)
+                .flat_map(|index| Self::get_chunk((upload_id, index)))
+                .collect(),
+            None => Self::get_content(key),
+        }
+    }
+
+    // Brief Implementation:
+    // just validate if data is too short and data equals order, as the market parameters say
+    fn validate_data(data: Vec<u8>, order: Vec<u8>) -> bool {
+        let parameters = Self::parameters();
+        data.len() >= parameters.min_data_len as usize && !(parameters.reject_order_content && data == order)
+    }
+
+    // validate_data() for the settlement of disputes, from the record of the data. The content is only read
+    // when it is as long as the order content, which is at most max_order_content_size bytes.
+    fn validate_record(key: u64, record: &DataRecord<T::AccountId, T::Balance, T::Hash, T::BlockNumber>, order: Vec<u8>) -> bool {
+        let parameters = Self::parameters();
+        record.size >= parameters.min_data_len
+            && !(parameters.reject_order_content && record.size as usize == order.len() && Self::get_from_chain(key) == order)
+    }
+
+    fn get_metadata(person: &T::AccountId, company: &T::AccountId, order_id: usize) -> Option<DataMetadata<T::AccountId, T::Hash>> {
+        let mut data_metadata: Option<DataMetadata<T::AccountId, T::Hash>> = None;
         let metadata_list = Self::get_data(person);
         for metadata in metadata_list {
             if &metadata.to_company == company && metadata.order_id == order_id {
@@ -249,3 +2661,1081 @@ impl<T: Trait> Module<T> {
     }
 
 }
+
+// ln(2) in Q16 fixed point
+const LN_2_Q16: u64 = 45_426;
+
+// -ln(u / 2^32) in Q16 fixed point, for u in (0, 2^32), with the mantissa of log2 interpolated linearly.
+fn neg_ln_q16(u: u32) -> u64 {
+    let u = rstd::cmp::max(u, 1);
+    let zeros = u.leading_zeros();
+    let mantissa = ((u as u64) << zeros) & 0x7fff_ffff;
+    let log2_q16 = (((31 - zeros) as u64) << 16) + (mantissa >> 15);
+    ((32u64 << 16) - log2_q16) * LN_2_Q16 >> 16
+}
+
+// The mean and the histogram of decrypted answers with Laplace noise, random gives the reporter's random bits.
+// The sum has sensitivity max_value and every histogram bucket sensitivity 1.
+pub fn noisy_aggregate<R: FnMut() -> u64>(values: &[u64], max_value: u64, buckets: &[u64], epsilon_millis: u32, mut random: R) -> (u64, Vec<u64>) {
+    let mut histogram = vec![0u64; buckets.len() + 1];
+    for &value in values {
+        histogram[buckets.iter().position(|&bound| value <= bound).unwrap_or(buckets.len())] += 1;
+    }
+    let mean = if values.is_empty() {
+        0
+    } else {
+        let sum = values.iter().map(|&value| value as u128).sum::<u128>();
+        let noisy_sum = rstd::cmp::max(sum as i128 + laplace_noise(random(), max_value, epsilon_millis) as i128, 0) as u128;
+        (noisy_sum / values.len() as u128) as u64
+    };
+    let histogram = histogram.into_iter()
+        .map(|n| rstd::cmp::max(n as i64 + laplace_noise(random(), 1, epsilon_millis), 0) as u64)
+        .collect();
+    (mean, histogram)
+}
+
+// Laplace noise for a statistic with the given sensitivity at epsilon_millis / 1000, from 64 random bits:
+// an exponentially distributed magnitude with scale sensitivity / epsilon and a random sign.
+fn laplace_noise(random: u64, sensitivity: u64, epsilon_millis: u32) -> i64 {
+    let exponential_q16 = neg_ln_q16((random >> 32) as u32);
+    let scale_q16 = ((sensitivity as u128) << 16) * 1000 / rstd::cmp::max(epsilon_millis, 1) as u128;
+    let magnitude = ((scale_q16 * exponential_q16 as u128) >> 32) as i64;
+    if random & 1 == 0 { magnitude } else { -magnitude }
+}
+
+client::decl_runtime_apis! {
+    // Queries wallets and companies can make through the `state_call` RPC.
+    pub trait DataSpaceApi<AccountId, Balance, Hash, BlockNumber> where
+        AccountId: Codec,
+        Balance: Codec,
+        Hash: Codec,
+        BlockNumber: Codec,
+    {
+        // what buying a person's upload to an order costs right now
+        fn current_price(company: AccountId, person: AccountId, order_id: usize) -> Option<Balance>;
+        // uploads to an order whose content also went to another order of the company, as (person, other order_id)
+        fn flagged_uploads(company: AccountId, order_id: usize) -> Vec<(AccountId, usize)>;
+        // the orders of the given companies the person can upload data for, as (company, order_id)
+        fn eligible_orders(person: AccountId, companies: Vec<AccountId>) -> Vec<(AccountId, usize)>;
+        // a page of all published orders, as (company, order_id)
+        fn list_orders(start: u32, count: u32) -> Vec<(AccountId, usize)>;
+        // a page of the orders published in a category, as (company, order_id)
+        fn list_orders_in_category(category: Vec<u8>, start: u32, count: u32) -> Vec<(AccountId, usize)>;
+        // a page of the orders carrying a tag, as (company, order_id)
+        fn list_orders_with_tag(tag: Vec<u8>, start: u32, count: u32) -> Vec<(AccountId, usize)>;
+        // the checks of up to max uploads waiting for validation, for a reporter to sign and submit
+        fn pending_checks(max: u32) -> Vec<UploadCheck<AccountId, Hash>>;
+        // the encrypted answers of the aggregate orders whose answer window closed, for a reporter to aggregate
+        fn aggregate_inputs() -> Vec<AggregateInput<AccountId>>;
+        // whether a reporter registered the aggregation key
+        fn is_aggregation_key(key: [u8; 32]) -> bool;
+        // the timeline of the dispute about a deal
+        fn dispute(deal_id: u64) -> Option<Dispute<AccountId, Hash, BlockNumber>>;
+    }
+}
+
+/// tests for this module
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use council::motions as council_motions;
+    use crate::technical_committee;
+
+    use runtime_io::with_externalities;
+    use primitives::{H256, Blake2Hasher};
+    use support::{impl_outer_origin, assert_ok, assert_noop};
+    use runtime_primitives::{
+        BuildStorage,
+        traits::{BlakeTwo256, IdentityLookup, Lazy, OnFinalize},
+        testing::{Digest, DigestItem, Header}
+    };
+
+    impl_outer_origin! {
+        pub enum Origin for Test {
+            council_motions<T>,
+            technical_committee<T>
+        }
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Digest = Digest;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type Event = ();
+        type Log = DigestItem;
+    }
+    impl balances::Trait for Test {
+        type Balance = u64;
+        type OnFreeBalanceZero = ();
+        type OnNewAccount = ();
+        type Event = ();
+        type TransactionPayment = ();
+        type DustRemoval = ();
+        type TransferPayment = ();
+    }
+    impl attestation::Trait for Test {
+        type Event = ();
+    }
+    impl Trait for Test {
+        type Event = ();
+        type Currency = balances::Module<Test>;
+        type Reporters = TestReporters;
+        type Signature = TestSignature;
+        type Governance = TestGovernance;
+    }
+
+    pub struct TestReporters;
+
+    impl Reporters for TestReporters {
+        type Id = u64;
+        type Signature = TestSignature;
+        fn is_reporter(who: &u64) -> bool {
+            *who == REPORTER
+        }
+    }
+
+    // The council and the technical committee have three members each, two are a majority.
+    pub struct TestGovernance;
+
+    impl Governance<Origin> for TestGovernance {
+        fn ensure_majority(origin: Origin) -> Result {
+            ensure_root(origin.clone()).or_else(|_| council_motions::ensure_council_members(origin, 2).map(|_| ()))
+        }
+        fn ensure_technical(origin: Origin) -> Result {
+            ensure_root(origin.clone()).or_else(|_| technical_committee::ensure_members(origin, 2).map(|_| ()))
+        }
+    }
+
+    fn council_majority() -> Origin {
+        council_motions::RawOrigin::Members(2).into()
+    }
+
+    fn technical_majority() -> Origin {
+        technical_committee::RawOrigin::Members(2).into()
+    }
+
+    // A signature which is valid when it names its signer and the message.
+    #[derive(Encode, Decode, Clone, PartialEq, Eq, Debug)]
+    pub struct TestSignature(u64, Vec<u8>);
+
+    impl Verify for TestSignature {
+        type Signer = u64;
+        fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &u64) -> bool {
+            self.0 == *signer && msg.get() == &self.1[..]
+        }
+    }
+
+    type DataSpace = Module<Test>;
+    type Balances = balances::Module<Test>;
+    type System = system::Module<Test>;
+    type Attestation = attestation::Module<Test>;
+
+    const COMPANY: u64 = 1;
+    const PERSON: u64 = 2;
+    const REPORTER: u64 = 7;
+
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+        t.extend(balances::GenesisConfig::<Test>{
+            balances: vec![(COMPANY, 1000), (PERSON, 1000), (3, 1000)],
+            transaction_base_fee: 0,
+            transaction_byte_fee: 0,
+            existential_deposit: 0,
+            transfer_fee: 0,
+            creation_fee: 0,
+            vesting: vec![],
+        }.build_storage().unwrap().0);
+        t.extend(GenesisConfig::<Test>{
+            max_data_size: 16,
+            max_order_content_size: 16,
+            byte_fee: 1,
+            deposit_per_byte: 2,
+            abandon_period: 10,
+            retention_period: 20,
+            delivery_period: 5,
+            min_company_bond: 0,
+            dispute_penalty: 50,
+            evidence_period: 3,
+            appeal_period: 5,
+            dispute_voting_period: 3,
+            appeal_voting_period: 4,
+            appeal_bond: 60,
+            min_bid_deposit: 20,
+            parameters: Default::default(),
+            max_upload_size: 40,
+            upload_timeout: 5,
+            companies: vec![],
+            orders: vec![],
+            uploads: vec![],
+        }.build_storage().unwrap().0);
+        t.into()
+    }
+
+    #[test]
+    fn publish_order_stores_order_and_charges_bytes() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_eq!(DataSpace::get_order(&COMPANY).len(), 1);
+            assert_eq!(Balances::free_balance(&COMPANY), 1000 - 9);
+        });
+    }
+
+    #[test]
+    fn upload_data_charges_bytes() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(
+                DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0),
+                "no orderform"
+            );
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_eq!(DataSpace::get_data(&PERSON).len(), 1);
+            assert_eq!(Balances::free_balance(&PERSON), 1000 - 5 - 8);
+            assert_eq!(Balances::reserved_balance(&PERSON), 8);
+        });
+    }
+
+    #[test]
+    fn uploads_which_cannot_be_paid_leave_nothing_behind() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            // the fee of 5 is affordable, the deposit of 8 on top of it is not
+            Balances::set_free_balance(&PERSON, 10);
+            assert!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0).is_err());
+            let values = vec![b"rice".to_vec()];
+            let root = DataSpace::merkle_root(&[DataSpace::field_leaf(0, &values[0])]);
+            assert!(DataSpace::upload_fields(Origin::signed(PERSON), b"d".to_vec(), COMPANY, 0, root, vec![(0, values[0].clone())], vec![vec![]]).is_err());
+            assert!(DataSpace::field_commitment(&(PERSON, COMPANY, 0)).is_none());
+            assert!(DataSpace::get_data(&PERSON).is_empty());
+            assert_eq!(Balances::free_balance(&PERSON), 10);
+            assert_eq!(Balances::reserved_balance(&PERSON), 0);
+        });
+    }
+
+    #[test]
+    fn erase_data_refunds_deposit() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::erase_data(Origin::signed(PERSON), COMPANY, 0));
+            assert!(DataSpace::get_data(&PERSON).is_empty());
+            assert_eq!(Balances::reserved_balance(&PERSON), 0);
+            assert_eq!(Balances::free_balance(&PERSON), 1000 - 5);
+        });
+    }
+
+    #[test]
+    fn validation_report_is_recorded_once() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            let check = UploadCheck {
+                person: PERSON,
+                company: COMPANY,
+                order_id: 0,
+                content_hash: BlakeTwo256::hash(b"rice"),
+                size_ok: true,
+                schema_ok: true,
+                duplicate: false,
+            };
+            assert_eq!(DataSpace::pending_checks(10), vec![check.clone()]);
+
+            // only reporters can report, and only with their own signature
+            let forged = Call::report_validation(check.clone(), 3, TestSignature(3, check.payload()));
+            match DataSpace::validate_unsigned(&forged) {
+                TransactionValidity::Invalid(_) => (),
+                _ => panic!("a report signed by someone else must not enter the pool"),
+            }
+            assert_noop!(DataSpace::report_validation(Origin::INHERENT, check.clone(), 3, TestSignature(3, check.payload())), "not a reporter");
+            assert_noop!(DataSpace::report_validation(Origin::INHERENT, check.clone(), REPORTER, TestSignature(REPORTER, b"forged".to_vec())),
+                "invalid report signature");
+            assert_noop!(DataSpace::report_validation(Origin::signed(REPORTER), check.clone(), REPORTER, TestSignature(REPORTER, check.payload())),
+                "bad origin: expected to be an inherent");
+
+            assert_ok!(DataSpace::report_validation(Origin::INHERENT, check.clone(), REPORTER, TestSignature(REPORTER, check.payload())));
+            assert!(DataSpace::pending_checks(10).is_empty());
+            assert!(DataSpace::get_report(&(PERSON, COMPANY, 0)).unwrap().passed());
+            let second = UploadCheck { schema_ok: false, ..check };
+            assert_noop!(
+                DataSpace::report_validation(Origin::INHERENT, second.clone(), REPORTER, TestSignature(REPORTER, second.payload())),
+                "upload is not waiting for validation"
+            );
+        });
+    }
+
+    #[test]
+    fn duplicate_upload_to_same_order_is_rejected() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_noop!(
+                DataSpace::upload_data(Origin::signed(3), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0),
+                "same data was already uploaded to this order"
+            );
+            // another order of the company takes the same content, but it gets flagged.
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"snacks".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::set_cross_order_check(Origin::signed(COMPANY), 1, true));
+            assert_ok!(DataSpace::upload_data(Origin::signed(3), b"d".to_vec(), b"rice".to_vec(), COMPANY, 1));
+            assert_eq!(DataSpace::flagged_uploads(&(COMPANY, 1)), vec![(3, 0)]);
+        });
+    }
+
+    #[test]
+    fn upload_requires_attested_claims() {
+        with_externalities(&mut new_test_ext(), || {
+            let adults = vec![Claim::AgeAtLeast(18)];
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, adults, vec![], b"food".to_vec(), vec![]));
+            assert_noop!(
+                DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0),
+                "you are not eligible for this order"
+            );
+            assert_ok!(Attestation::add_attester(Origin::ROOT, 3));
+            assert_ok!(Attestation::attest(Origin::signed(3), PERSON, Claim::AgeAtLeast(21)));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_noop!(
+                DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"bread".to_vec(), COMPANY, 0),
+                "you already uploaded data to this order"
+            );
+        });
+    }
+
+    #[test]
+    fn purchase_is_held_in_escrow_until_confirmed() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 100);
+            assert_noop!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0), "you already bought this data");
+
+            assert_noop!(DataSpace::confirm_data(Origin::signed(3), 0, 5), "you did not buy this data");
+            assert_ok!(DataSpace::confirm_data(Origin::signed(COMPANY), 0, 5));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 0);
+            assert_eq!(Balances::free_balance(&PERSON), 1000 - 5 - 8 + 100);
+        });
+    }
+
+    #[test]
+    fn sealed_bid_auction_sells_exclusively() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::list_exclusive(Origin::signed(PERSON), COMPANY, 0, 50, 2, 2));
+            assert_noop!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0), "data is being auctioned");
+
+            let salt = H256::from([1u8; 32]);
+            assert_ok!(DataSpace::commit_bid(Origin::signed(COMPANY), PERSON, COMPANY, 0, BlakeTwo256::hash_of(&(80u64, salt))));
+            assert_ok!(DataSpace::commit_bid(Origin::signed(3), PERSON, COMPANY, 0, BlakeTwo256::hash_of(&(120u64, salt))));
+            System::set_block_number(2);
+            assert_ok!(DataSpace::reveal_bid(Origin::signed(COMPANY), PERSON, COMPANY, 0, 80, salt));
+            assert_ok!(DataSpace::reveal_bid(Origin::signed(3), PERSON, COMPANY, 0, 120, salt));
+            <DataSpace as OnFinalize<u64>>::on_finalize(4);
+
+            assert_eq!(DataSpace::exclusive_to(&(PERSON, COMPANY, 0)), Some(3));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 0);
+            assert_eq!(Balances::reserved_balance(&3), 120);
+            assert_noop!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0), "data was sold exclusively");
+            assert_ok!(DataSpace::confirm_data(Origin::signed(3), 0, 3));
+
+            // the winner's own order 0 is another deal with the same person
+            assert_ok!(DataSpace::publish_order(Origin::signed(3), b"food".to_vec(), b"drinks".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"tea".to_vec(), 3, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(3), PERSON, 0));
+            assert_eq!(DataSpace::deal_id(&(3, PERSON, COMPANY, 0)), Some(0));
+            assert_eq!(DataSpace::deal_id(&(3, PERSON, 3, 0)), Some(1));
+            assert_eq!(DataSpace::get_deal(0).unwrap().status, DealStatus::Confirmed);
+            assert_eq!(DataSpace::get_deal(1).unwrap().status, DealStatus::Paid);
+        });
+    }
+
+    #[test]
+    fn erased_uploads_can_be_uploaded_and_sold_again() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_ok!(DataSpace::confirm_data(Origin::signed(COMPANY), 0, 5));
+            assert_ok!(DataSpace::erase_data(Origin::signed(PERSON), COMPANY, 0));
+            assert!(DataSpace::deal_id(&(COMPANY, PERSON, COMPANY, 0)).is_none());
+
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"beans".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_eq!(DataSpace::deal_id(&(COMPANY, PERSON, COMPANY, 0)), Some(1));
+            assert_eq!(DataSpace::get_deal(0).unwrap().status, DealStatus::Confirmed);
+        });
+    }
+
+    #[test]
+    fn erasing_an_exclusive_sale_frees_the_upload() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::list_exclusive(Origin::signed(PERSON), COMPANY, 0, 50, 2, 2));
+            let salt = H256::from([1u8; 32]);
+            assert_ok!(DataSpace::commit_bid(Origin::signed(3), PERSON, COMPANY, 0, BlakeTwo256::hash_of(&(120u64, salt))));
+            System::set_block_number(2);
+            assert_ok!(DataSpace::reveal_bid(Origin::signed(3), PERSON, COMPANY, 0, 120, salt));
+            <DataSpace as OnFinalize<u64>>::on_finalize(4);
+            assert_eq!(DataSpace::exclusive_to(&(PERSON, COMPANY, 0)), Some(3));
+            assert_ok!(DataSpace::confirm_data(Origin::signed(3), 0, 3));
+
+            assert_ok!(DataSpace::erase_data(Origin::signed(PERSON), COMPANY, 0));
+            assert!(DataSpace::exclusive_to(&(PERSON, COMPANY, 0)).is_none());
+            assert!(DataSpace::deal_id(&(3, PERSON, COMPANY, 0)).is_none());
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"beans".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+        });
+    }
+
+    #[test]
+    fn sealed_bids_are_bonded_and_bounded() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            // a reserve price below min_bid_deposit still bonds min_bid_deposit
+            assert_ok!(DataSpace::list_exclusive(Origin::signed(PERSON), COMPANY, 0, 0, 2, 2));
+            assert_ok!(DataSpace::commit_bid(Origin::signed(COMPANY), PERSON, COMPANY, 0, H256::from([1u8; 32])));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 20);
+
+            let upload = (PERSON, COMPANY, 0);
+            <Bidders<Test>>::insert(&upload, (100..100 + MAX_BIDDERS as u64).collect::<Vec<u64>>());
+            assert_noop!(DataSpace::commit_bid(Origin::signed(3), PERSON, COMPANY, 0, H256::from([1u8; 32])), "too many bidders");
+        });
+    }
+
+    #[test]
+    fn subscribers_are_paid_for_periods_with_fresh_data() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::offer_subscription(Origin::signed(COMPANY), 0, 5, 30));
+            assert_ok!(DataSpace::fund_subscriptions(Origin::signed(COMPANY), 0, 50));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::subscribe(Origin::signed(PERSON), COMPANY, 0));
+
+            assert_ok!(DataSpace::update_data(Origin::signed(PERSON), b"d".to_vec(), b"noodles".to_vec(), COMPANY, 0));
+            <DataSpace as OnFinalize<u64>>::on_finalize(5);
+            assert_eq!(DataSpace::allowance(&(COMPANY, 0)), 20);
+            assert_eq!(DataSpace::get_subscription(&(PERSON, COMPANY, 0)).unwrap().paid_periods, 1);
+
+            // nothing submitted in the second period
+            <DataSpace as OnFinalize<u64>>::on_finalize(10);
+            assert_eq!(DataSpace::get_subscription(&(PERSON, COMPANY, 0)).unwrap().missed_periods, 1);
+
+            // the allowance can't cover a third period
+            assert_ok!(DataSpace::update_data(Origin::signed(PERSON), b"d".to_vec(), b"bread".to_vec(), COMPANY, 0));
+            <DataSpace as OnFinalize<u64>>::on_finalize(15);
+            assert!(DataSpace::get_subscription(&(PERSON, COMPANY, 0)).is_none());
+
+            assert_noop!(DataSpace::cancel_subscription(Origin::signed(COMPANY), PERSON, 0), "no subscription");
+        });
+    }
+
+    #[test]
+    fn unused_allowance_can_be_withdrawn() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::offer_subscription(Origin::signed(COMPANY), 0, 5, 30));
+            assert_ok!(DataSpace::fund_subscriptions(Origin::signed(COMPANY), 0, 50));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::subscribe(Origin::signed(PERSON), COMPANY, 0));
+            assert_ok!(DataSpace::cancel_subscription(Origin::signed(COMPANY), PERSON, 0));
+
+            assert_noop!(DataSpace::withdraw_allowance(Origin::signed(COMPANY), 0, 60), "allowance is too small");
+            assert_ok!(DataSpace::withdraw_allowance(Origin::signed(COMPANY), 0, 50));
+            assert_eq!(DataSpace::allowance(&(COMPANY, 0)), 0);
+            assert_eq!(Balances::reserved_balance(&COMPANY), 0);
+        });
+    }
+
+    #[test]
+    fn buy_all_resumes_where_it_stopped() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::upload_data(Origin::signed(3), b"d".to_vec(), b"bread".to_vec(), COMPANY, 0));
+
+            assert_ok!(DataSpace::buy_all(Origin::signed(COMPANY), 0, 1));
+            assert!(DataSpace::deal_id(&(COMPANY, PERSON, COMPANY, 0)).is_some());
+            assert!(DataSpace::deal_id(&(COMPANY, 3, COMPANY, 0)).is_none());
+
+            assert_ok!(DataSpace::buy_all(Origin::signed(COMPANY), 0, 10));
+            assert!(DataSpace::deal_id(&(COMPANY, 3, COMPANY, 0)).is_some());
+            assert_eq!(Balances::reserved_balance(&COMPANY), 200);
+            assert_eq!(Balances::free_balance(&COMPANY), 1000 - 9 - 200 - 2 * BULK_ITEM_BYTES as u64);
+        });
+    }
+
+    #[test]
+    fn buy_all_skips_erased_and_own_uploads() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(COMPANY), b"d".to_vec(), b"soup".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::upload_data(Origin::signed(3), b"d".to_vec(), b"bread".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::erase_data(Origin::signed(PERSON), COMPANY, 0));
+            assert_eq!(DataSpace::uploader_count(&(COMPANY, 0)), 3);
+            assert!(DataSpace::uploader_at(&(COMPANY, 0, 1)).is_none());
+
+            assert_ok!(DataSpace::buy_all(Origin::signed(COMPANY), 0, 10));
+            assert!(DataSpace::deal_id(&(COMPANY, COMPANY, COMPANY, 0)).is_none());
+            assert!(DataSpace::deal_id(&(COMPANY, 3, COMPANY, 0)).is_some());
+            assert_eq!(DataSpace::buy_cursor(&(COMPANY, 0)), 3);
+            // only the upload which was bought paid for its item
+            assert_eq!(Balances::reserved_balance(&COMPANY), 100 + 8);
+            assert_eq!(Balances::free_balance(&COMPANY), 1000 - 9 - 5 - 8 - 100 - BULK_ITEM_BYTES as u64);
+        });
+    }
+
+    #[test]
+    fn buy_all_comes_back_to_auctioned_uploads() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::upload_data(Origin::signed(3), b"d".to_vec(), b"bread".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::list_exclusive(Origin::signed(PERSON), COMPANY, 0, 50, 2, 2));
+
+            assert_ok!(DataSpace::buy_all(Origin::signed(COMPANY), 0, 10));
+            assert!(DataSpace::deal_id(&(COMPANY, 3, COMPANY, 0)).is_some());
+            assert_eq!(DataSpace::buy_cursor(&(COMPANY, 0)), 0);
+
+            // nobody bid, the upload can be bought now
+            <DataSpace as OnFinalize<u64>>::on_finalize(4);
+            assert_ok!(DataSpace::buy_all(Origin::signed(COMPANY), 0, 10));
+            assert!(DataSpace::deal_id(&(COMPANY, PERSON, COMPANY, 0)).is_some());
+            assert_eq!(DataSpace::buy_cursor(&(COMPANY, 0)), 2);
+        });
+    }
+
+    #[test]
+    fn orders_are_listed_by_category() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![b"diet".to_vec()]));
+            assert_ok!(DataSpace::publish_order(Origin::signed(3), b"steps".to_vec(), b"walks".to_vec(), 10, vec![], vec![], b"health".to_vec(), vec![]));
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"snacks".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+
+            assert_eq!(DataSpace::list_orders(0, 10).len(), 3);
+            assert_eq!(DataSpace::list_orders(2, 10).len(), 1);
+            assert_eq!(DataSpace::list_orders_in_category(b"food".to_vec(), 0, 10), vec![(COMPANY, 0), (COMPANY, 1)]);
+            assert_eq!(DataSpace::list_orders_in_category(b"food".to_vec(), 1, 1), vec![(COMPANY, 1)]);
+            assert_eq!(DataSpace::list_orders_in_category(b"health".to_vec(), 0, 10), vec![(3, 0)]);
+            assert_eq!(DataSpace::list_orders(1, 1), vec![(3, 0)]);
+            assert_eq!(DataSpace::list_orders_with_tag(b"diet".to_vec(), 0, 10), vec![(COMPANY, 0)]);
+            assert!(DataSpace::list_orders_with_tag(b"sport".to_vec(), 0, 10).is_empty());
+        });
+    }
+
+    #[test]
+    fn price_follows_the_submission_order() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::set_pricing(Origin::signed(COMPANY), 0, Pricing::LinearDecrease { step: 30, floor: 50 }));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::upload_data(Origin::signed(3), b"d".to_vec(), b"bread".to_vec(), COMPANY, 0));
+
+            assert_eq!(DataSpace::current_price(&COMPANY, &PERSON, 0), Some(100));
+            assert_eq!(DataSpace::current_price(&COMPANY, &3, 0), Some(70));
+            // buying the later upload first does not change what either costs
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), 3, 0));
+            assert_eq!(DataSpace::get_deal(0).unwrap().price, 70);
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_eq!(DataSpace::get_deal(1).unwrap().price, 100);
+
+            assert_ok!(DataSpace::set_pricing(Origin::signed(COMPANY), 0, Pricing::Decay { interval: 10, step: 5, floor: 0 }));
+            System::set_block_number(25);
+            assert_eq!(DataSpace::current_price(&COMPANY, &PERSON, 0), Some(90));
+        });
+    }
+
+    #[test]
+    fn payout_is_scaled_by_rating() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::set_rating_table(Origin::signed(COMPANY), 0, vec![20, 40, 60, 80, 100]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+
+            assert_noop!(DataSpace::set_rating_table(Origin::signed(COMPANY), 0, vec![0, 0, 0, 0, 100]), "percentage is below the minimum payout");
+            assert_noop!(DataSpace::set_rating_table(Origin::signed(COMPANY), 0, vec![100, 80, 60, 40, 20]), "a better rating can't pay less");
+            // a table set after the purchase does not apply to it
+            assert_ok!(DataSpace::set_rating_table(Origin::signed(COMPANY), 0, vec![20, 20, 20, 20, 20]));
+
+            assert_noop!(DataSpace::confirm_data(Origin::signed(COMPANY), 0, 6), "rating must be between 1 and 5");
+            assert_ok!(DataSpace::confirm_data(Origin::signed(COMPANY), 0, 2));
+            assert_eq!(Balances::free_balance(&PERSON), 1000 - 5 - 8 + 40);
+            assert_eq!(Balances::free_balance(&COMPANY), 1000 - 9 - 40);
+            assert_eq!(Balances::reserved_balance(&COMPANY), 0);
+            assert_eq!(DataSpace::reputation(&PERSON), (1, 2));
+        });
+    }
+
+    #[test]
+    fn aggregate_shares_are_claimed_for_valid_answers() {
+        with_externalities(&mut new_test_ext(), || {
+            let aggregation_key = [9u8; 32];
+            assert_noop!(DataSpace::register_aggregation_key(Origin::INHERENT, aggregation_key, 8, TestSignature(8, aggregation_key_payload(&aggregation_key))), "not a reporter");
+            assert_ok!(DataSpace::register_aggregation_key(Origin::INHERENT, aggregation_key, REPORTER, TestSignature(REPORTER, aggregation_key_payload(&aggregation_key))));
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_noop!(DataSpace::open_aggregate(Origin::signed(COMPANY), 0, 10, vec![3, 6], 1000, 2, 90, 2, [1u8; 32]), "unknown aggregation key");
+            assert_ok!(DataSpace::open_aggregate(Origin::signed(COMPANY), 0, 10, vec![3, 6], 1000, 2, 90, 2, aggregation_key));
+
+            assert_noop!(DataSpace::submit_answer(Origin::signed(PERSON), COMPANY, 0, vec![2]), "answer is not an encrypted value");
+            assert_ok!(DataSpace::submit_answer(Origin::signed(PERSON), COMPANY, 0, vec![1; ANSWER_LEN]));
+            assert_noop!(DataSpace::submit_answer(Origin::signed(PERSON), COMPANY, 0, vec![1; ANSWER_LEN]), "you already answered");
+            assert_ok!(DataSpace::submit_answer(Origin::signed(3), COMPANY, 0, vec![2; ANSWER_LEN]));
+            assert_ok!(DataSpace::submit_answer(Origin::signed(8), COMPANY, 0, vec![3; ANSWER_LEN]));
+            assert!(DataSpace::aggregate_inputs().is_empty());
+
+            System::set_block_number(2);
+            assert_noop!(DataSpace::submit_answer(Origin::signed(COMPANY), COMPANY, 0, vec![4; ANSWER_LEN]), "answer window is over");
+            let inputs = DataSpace::aggregate_inputs();
+            assert_eq!(inputs.len(), 1);
+            assert_eq!(inputs[0].answers[2], (8, vec![3; ANSWER_LEN]));
+
+            // the answer of account 8 did not decrypt
+            let report = AggregateReport { company: COMPANY, order_id: 0, mean: 5, histogram: vec![1, 0, 1], rejected: vec![2] };
+            assert_noop!(DataSpace::report_aggregate(Origin::INHERENT, report.clone(), 8, TestSignature(8, report.payload())), "not a reporter");
+            assert_ok!(DataSpace::report_aggregate(Origin::INHERENT, report.clone(), REPORTER, TestSignature(REPORTER, report.payload())));
+            assert_eq!(DataSpace::aggregate_result(&(COMPANY, 0)).unwrap().contributors, 2);
+            assert_eq!(Balances::reserved_balance(&COMPANY), 90);
+
+            assert_ok!(DataSpace::claim_share(Origin::signed(3), COMPANY, 0));
+            assert_eq!(Balances::free_balance(&3), 1000 + 45);
+            assert_noop!(DataSpace::claim_share(Origin::signed(3), COMPANY, 0), "nothing to claim");
+            assert_noop!(DataSpace::claim_share(Origin::signed(8), COMPANY, 0), "your answer was rejected");
+            assert_ok!(DataSpace::claim_share(Origin::signed(PERSON), COMPANY, 0));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 0);
+        });
+    }
+
+    #[test]
+    fn unpublished_aggregates_expire() {
+        with_externalities(&mut new_test_ext(), || {
+            let aggregation_key = [9u8; 32];
+            assert_ok!(DataSpace::register_aggregation_key(Origin::INHERENT, aggregation_key, REPORTER, TestSignature(REPORTER, aggregation_key_payload(&aggregation_key))));
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_noop!(DataSpace::open_aggregate(Origin::signed(COMPANY), 0, 10, vec![], 1000, 2, 90, 101, aggregation_key), "answer window is too long");
+            assert_noop!(DataSpace::open_aggregate(Origin::signed(COMPANY), 0, 10, vec![], 1000, 2, 0, 2, aggregation_key), "budget can't be zero");
+            assert_noop!(DataSpace::open_aggregate(Origin::signed(COMPANY), 0, 10, vec![], 1001, 2, 90, 2, aggregation_key), "epsilon is too large");
+            assert_noop!(DataSpace::open_aggregate(Origin::signed(COMPANY), 0, 10, vec![], 1000, 0, 90, 2, aggregation_key), "too few contributors to hide anyone");
+            assert_ok!(DataSpace::open_aggregate(Origin::signed(COMPANY), 0, 10, vec![], 1000, 2, 90, 2, aggregation_key));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 90 + 2 * AGGREGATE_DEPOSIT_BYTES);
+            assert_ok!(DataSpace::submit_answer(Origin::signed(PERSON), COMPANY, 0, vec![1; ANSWER_LEN]));
+
+            // the reporter had until block 12 to publish the aggregate
+            <DataSpace as OnFinalize<u64>>::on_finalize(12);
+            assert!(DataSpace::pending_aggregates().is_empty());
+            assert!(DataSpace::aggregate_order(&(COMPANY, 0)).is_none());
+            assert!(DataSpace::answer(&(COMPANY, 0, 0)).is_none());
+            assert!(DataSpace::answer_index(&(COMPANY, 0, PERSON)).is_none());
+            assert_eq!(DataSpace::answer_count(&(COMPANY, 0)), 0);
+            assert_eq!(Balances::reserved_balance(&COMPANY), 0);
+            assert_ok!(DataSpace::open_aggregate(Origin::signed(COMPANY), 0, 10, vec![], 1000, 2, 90, 2, aggregation_key));
+        });
+    }
+
+    #[test]
+    fn laplace_noise_is_symmetric_and_scaled() {
+        assert_eq!(neg_ln_q16(1 << 31), LN_2_Q16);
+        assert_eq!(laplace_noise(1 << 63, 0, 1000), 0);
+        assert!(laplace_noise(1 << 62, 100, 1000) > 0);
+        assert!(laplace_noise((1 << 62) | 1, 100, 1000) < 0);
+        // the largest random value draws no noise
+        assert_eq!(noisy_aggregate(&[2, 7], 10, &[3, 6], 1000, || 0xffff_ffff_0000_0000), (4, vec![1, 0, 1]));
+    }
+
+    #[test]
+    fn chunked_upload_is_checked_against_root() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            let chunks = vec![b"0123456789".to_vec(), b"abcdefghij".to_vec(), b"xyz".to_vec()];
+            let leaves: Vec<H256> = chunks.iter().map(|c| DataSpace::chunk_leaf(c)).collect();
+            let root = DataSpace::merkle_root(&leaves);
+            assert_ok!(DataSpace::begin_upload(Origin::signed(PERSON), b"big".to_vec(), COMPANY, 0, 23, 3, root));
+
+            assert_ok!(DataSpace::upload_chunk(Origin::signed(PERSON), 0, 2, chunks[2].clone()));
+            assert_ok!(DataSpace::upload_chunk(Origin::signed(PERSON), 0, 0, b"wrong data".to_vec()));
+            assert_noop!(DataSpace::finalize_upload(Origin::signed(PERSON), 0), "upload is incomplete");
+            assert_ok!(DataSpace::upload_chunk(Origin::signed(PERSON), 0, 1, chunks[1].clone()));
+            assert_noop!(DataSpace::finalize_upload(Origin::signed(PERSON), 0), "chunks do not match the root hash");
+            // resend the broken chunk
+            assert_ok!(DataSpace::upload_chunk(Origin::signed(PERSON), 0, 0, chunks[0].clone()));
+            assert_eq!(Balances::reserved_balance(&PERSON), 2 * (23 + 3 * 64));
+
+            assert_ok!(DataSpace::finalize_upload(Origin::signed(PERSON), 0));
+            assert_eq!(DataSpace::get_from_chain(0), b"0123456789abcdefghijxyz".to_vec());
+            assert!(DataSpace::chunked_upload(0).is_none());
+            // the upload is committed to its root, its chunks are kept as they are
+            assert_eq!(DataSpace::get_data(&PERSON)[0].content_hash, root);
+            assert_eq!(DataSpace::chunk_hash((0, 0)), Some(leaves[0]));
+            assert_eq!(Balances::reserved_balance(&PERSON), 2 * (23 + 3 * 64));
+
+            assert_ok!(DataSpace::erase_data(Origin::signed(PERSON), COMPANY, 0));
+            assert!(DataSpace::get_chunk((0, 0)).is_empty());
+            assert!(DataSpace::chunk_hash((0, 0)).is_none());
+            assert_eq!(Balances::reserved_balance(&PERSON), 0);
+        });
+    }
+
+    #[test]
+    fn unfinished_upload_times_out() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::begin_upload(Origin::signed(PERSON), b"big".to_vec(), COMPANY, 0, 20, 2, H256::from([7u8; 32])));
+            assert_ok!(DataSpace::upload_chunk(Origin::signed(PERSON), 0, 0, b"0123456789".to_vec()));
+            assert_eq!(Balances::reserved_balance(&PERSON), 2 * (10 + 64));
+
+            <DataSpace as OnFinalize<u64>>::on_finalize(5);
+            assert!(DataSpace::chunked_upload(0).is_none());
+            assert!(DataSpace::get_chunk((0, 0)).is_empty());
+            assert_eq!(Balances::reserved_balance(&PERSON), 0);
+        });
+    }
+
+    #[test]
+    fn fields_are_disclosed_with_proofs() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::set_mandatory_fields(Origin::signed(COMPANY), 0, vec![1]));
+            let values = vec![b"name".to_vec(), b"rice".to_vec(), b"age".to_vec(), b"city".to_vec()];
+            let leaves: Vec<H256> = values.iter().enumerate().map(|(i, v)| DataSpace::field_leaf(i as u32, v)).collect();
+            let root = DataSpace::merkle_root(&leaves);
+            let proof_of_1 = vec![leaves[0], BlakeTwo256::hash_of(&(MERKLE_NODE, leaves[2], leaves[3]))];
+            let proof_of_2 = vec![leaves[3], BlakeTwo256::hash_of(&(MERKLE_NODE, leaves[0], leaves[1]))];
+
+            assert_noop!(DataSpace::upload_fields(Origin::signed(PERSON), b"d".to_vec(), COMPANY, 0, root, vec![(2, values[2].clone())], vec![proof_of_2.clone()]),
+                "mandatory field is not disclosed");
+            assert_noop!(DataSpace::upload_fields(Origin::signed(PERSON), b"d".to_vec(), COMPANY, 0, root, vec![(1, values[2].clone())], vec![proof_of_1.clone()]),
+                "field is not part of the root");
+            assert_ok!(DataSpace::upload_fields(Origin::signed(PERSON), b"d".to_vec(), COMPANY, 0, root,
+                vec![(1, values[1].clone()), (2, values[2].clone())], vec![proof_of_1, proof_of_2]));
+            assert_eq!(DataSpace::field_commitment(&(PERSON, COMPANY, 0)), Some((root, vec![1, 2])));
+
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 100);
+        });
+    }
+
+    #[test]
+    fn buyers_can_upgrade_to_a_newer_version() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_ok!(DataSpace::confirm_data(Origin::signed(COMPANY), 0, 5));
+            assert_noop!(DataSpace::buy_upgrade(Origin::signed(COMPANY), 0), "you already have the latest version");
+
+            System::set_block_number(3);
+            assert_ok!(DataSpace::update_data(Origin::signed(PERSON), b"d".to_vec(), b"noodles".to_vec(), COMPANY, 0));
+            let versions = DataSpace::versions(&(PERSON, COMPANY, 0));
+            assert_eq!(versions.len(), 2);
+            assert_eq!(versions[1].uploaded_at, 3);
+            assert_eq!(DataSpace::get_deal(0).unwrap().version, 0);
+
+            assert_ok!(DataSpace::set_upgrade_price(Origin::signed(COMPANY), 0, 30));
+            // upgrades are sales like any other
+            assert_ok!(DataSpace::set_fees(Origin::ROOT, 1, 2, 10, 50, 60, 20));
+            assert_noop!(DataSpace::buy_upgrade(Origin::signed(COMPANY), 0), "bond is too small");
+            assert_ok!(DataSpace::set_fees(Origin::ROOT, 1, 2, 0, 50, 60, 20));
+            assert_ok!(DataSpace::buy_upgrade(Origin::signed(COMPANY), 0));
+            // the upgrade is a deal of its own, the confirmed one stays as it was
+            assert_eq!(DataSpace::get_deal(0).unwrap().status, DealStatus::Confirmed);
+            assert_eq!(DataSpace::deal_id(&(COMPANY, PERSON, COMPANY, 0)), Some(1));
+            let deal = DataSpace::get_deal(1).unwrap();
+            assert_eq!(deal.version, 1);
+            assert_eq!(deal.content_hash, versions[1].content_hash);
+            assert_eq!(Balances::reserved_balance(&COMPANY), 30);
+        });
+    }
+
+    #[test]
+    fn delivery_receipts_are_recorded_on_deals() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::upload_data(Origin::signed(3), b"d".to_vec(), b"beans".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), 3, 0));
+
+            let content_hash = BlakeTwo256::hash(b"rice");
+            let message = (DELIVERY_CONTEXT, 0u64, content_hash).encode();
+            assert_noop!(DataSpace::submit_receipt(Origin::signed(PERSON), 0, content_hash, TestSignature(3, message.clone())),
+                "invalid receipt signature");
+            assert_ok!(DataSpace::submit_receipt(Origin::signed(PERSON), 0, content_hash, TestSignature(COMPANY, message)));
+            assert_eq!(DataSpace::get_deal(0).unwrap().receipt, Some((content_hash, 0)));
+            assert_noop!(DataSpace::acknowledge_delivery(Origin::signed(COMPANY), 0, content_hash), "delivery was already acknowledged");
+
+            assert_noop!(DataSpace::acknowledge_delivery(Origin::signed(COMPANY), 1, content_hash), "receipt is for another version of the data");
+            assert_ok!(DataSpace::acknowledge_delivery(Origin::signed(COMPANY), 1, BlakeTwo256::hash(b"beans")));
+        });
+    }
+
+    #[test]
+    fn people_dispute_unconfirmed_purchases() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::post_bond(Origin::signed(COMPANY), 80));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_noop!(DataSpace::withdraw_bond(Origin::signed(COMPANY), 80), "deals are still held in escrow");
+            assert_noop!(DataSpace::dispute_company(Origin::signed(PERSON), 0, DisputeReason::NeverConfirmed),
+                "company still has time to confirm");
+
+            System::set_block_number(5);
+            assert_noop!(DataSpace::dispute_company(Origin::signed(3), 0, DisputeReason::NeverConfirmed), "you did not sell this data");
+            assert_ok!(DataSpace::dispute_company(Origin::signed(PERSON), 0, DisputeReason::NeverConfirmed));
+            assert_noop!(DataSpace::confirm_data(Origin::signed(COMPANY), 0, 5), "deal is already settled");
+            assert_ok!(DataSpace::submit_evidence(Origin::signed(COMPANY), 0, H256::from([7u8; 32]), b"late".to_vec()));
+            assert_noop!(DataSpace::submit_evidence(Origin::signed(3), 0, H256::from([7u8; 32]), vec![]),
+                "you are not part of this dispute");
+
+            System::set_block_number(8);
+            assert_noop!(DataSpace::submit_evidence(Origin::signed(PERSON), 0, H256::from([7u8; 32]), vec![]),
+                "evidence window is closed");
+            // the evidence sends the dispute to the council, which does not decide it in time
+            <DataSpace as OnFinalize<u64>>::on_finalize(8);
+            assert_eq!(DataSpace::get_dispute(0).unwrap().council_end, Some(11));
+            let before = Balances::free_balance(&PERSON);
+            System::set_block_number(11);
+            assert_noop!(DataSpace::decide_dispute(council_majority(), 0, false), "council's time to decide is over");
+            <DataSpace as OnFinalize<u64>>::on_finalize(11);
+            assert_eq!(Balances::free_balance(&PERSON), before + 100 + 50);
+            let dispute = DataSpace::get_dispute(0).unwrap();
+            assert_eq!(dispute.evidence.len(), 1);
+            assert_eq!(dispute.verdict, Some((PERSON, 11)));
+            assert_eq!(DataSpace::company_bond(&COMPANY), 30);
+            assert_noop!(DataSpace::withdraw_bond(Origin::signed(COMPANY), 30), "a verdict can still be appealed");
+            System::set_block_number(16);
+            assert_ok!(DataSpace::withdraw_bond(Origin::signed(COMPANY), 30));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 0);
+        });
+    }
+
+    #[test]
+    fn council_decides_disputes_with_evidence() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_ok!(DataSpace::tip_off_data(Origin::signed(COMPANY), 0));
+            assert_noop!(DataSpace::decide_dispute(council_majority(), 0, false), "dispute was not referred to the council");
+            assert_ok!(DataSpace::submit_evidence(Origin::signed(COMPANY), 0, H256::from([7u8; 32]), b"fake".to_vec()));
+
+            <DataSpace as OnFinalize<u64>>::on_finalize(3);
+            // only a motion passed by a majority of the council decides
+            assert!(DataSpace::decide_dispute(Origin::signed(4), 0, false).is_err());
+            assert!(DataSpace::decide_dispute(council_motions::RawOrigin::Members(1).into(), 0, false).is_err());
+            assert!(DataSpace::decide_dispute(technical_majority(), 0, false).is_err());
+            // the data passes the automatic check, the council decides anyway
+            assert_ok!(DataSpace::decide_dispute(council_majority(), 0, false));
+            assert_eq!(DataSpace::get_deal(0).unwrap().status, DealStatus::Refunded);
+            assert_eq!(DataSpace::get_dispute(0).unwrap().verdict, Some((COMPANY, 0)));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 0);
+            assert_noop!(DataSpace::decide_dispute(council_majority(), 0, true), "dispute was already decided");
+            // the end of the council's time changes nothing
+            <DataSpace as OnFinalize<u64>>::on_finalize(3);
+            assert_eq!(DataSpace::get_dispute(0).unwrap().verdict, Some((COMPANY, 0)));
+        });
+    }
+
+    #[test]
+    fn disputes_are_judged_against_the_sold_version() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            // the new content only repeats the order, the content which was sold is still legal
+            assert_ok!(DataSpace::update_data(Origin::signed(PERSON), b"d".to_vec(), b"meals".to_vec(), COMPANY, 0));
+            assert_eq!(DataSpace::get_from_chain(0), b"rice".to_vec());
+
+            assert_ok!(DataSpace::tip_off_data(Origin::signed(COMPANY), 0));
+            <DataSpace as OnFinalize<u64>>::on_finalize(3);
+            assert_eq!(DataSpace::get_dispute(0).unwrap().verdict, Some((PERSON, 0)));
+
+            // the sold content goes once its retention period is over
+            <DataSpace as OnFinalize<u64>>::on_finalize(20);
+            assert!(DataSpace::get_from_chain(0).is_empty());
+            assert!(DataSpace::get_metadata(&PERSON, &COMPANY, 0).is_some());
+        });
+    }
+
+    #[test]
+    fn council_overturns_an_appealed_verdict() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::post_bond(Origin::signed(COMPANY), 80));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_ok!(DataSpace::tip_off_data(Origin::signed(COMPANY), 0));
+            <DataSpace as OnFinalize<u64>>::on_finalize(3);
+            assert_eq!(DataSpace::get_dispute(0).unwrap().verdict, Some((PERSON, 0)));
+            let company_free = Balances::free_balance(&COMPANY);
+
+            System::set_block_number(2);
+            assert_noop!(DataSpace::appeal(Origin::signed(PERSON), 0), "only the losing side can appeal");
+            assert_ok!(DataSpace::appeal(Origin::signed(COMPANY), 0));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 30 + 60);
+            assert!(DataSpace::decide_appeal(Origin::signed(4), 0, true).is_err());
+            assert_ok!(DataSpace::decide_appeal(council_majority(), 0, true));
+            assert_eq!(DataSpace::get_appeal(0).unwrap().overturned, Some(true));
+            assert_eq!(Balances::free_balance(&COMPANY), company_free + 100);
+            assert_eq!(DataSpace::company_bond(&COMPANY), 80);
+            assert_eq!(Balances::reserved_balance(&COMPANY), 80);
+            assert_eq!(DataSpace::get_deal(0).unwrap().status, DealStatus::Refunded);
+            assert_eq!(DataSpace::get_dispute(0).unwrap().verdict, Some((COMPANY, 2)));
+            assert_noop!(DataSpace::decide_appeal(council_majority(), 0, false), "appeal was already decided");
+
+            // the end of the voting period changes nothing
+            <DataSpace as OnFinalize<u64>>::on_finalize(6);
+            assert_eq!(DataSpace::get_appeal(0).unwrap().overturned, Some(true));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 80);
+        });
+    }
+
+    #[test]
+    fn undecided_appeals_are_upheld() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_ok!(DataSpace::tip_off_data(Origin::signed(COMPANY), 0));
+            <DataSpace as OnFinalize<u64>>::on_finalize(3);
+            let person_free = Balances::free_balance(&PERSON);
+
+            assert_ok!(DataSpace::appeal(Origin::signed(COMPANY), 0));
+            assert_noop!(DataSpace::withdraw_bond(Origin::signed(COMPANY), 0), "appeals are still open");
+            System::set_block_number(4);
+            assert_noop!(DataSpace::decide_appeal(council_majority(), 0, true), "voting on the appeal is over");
+            <DataSpace as OnFinalize<u64>>::on_finalize(4);
+            assert_eq!(DataSpace::get_appeal(0).unwrap().overturned, Some(false));
+            assert_noop!(DataSpace::withdraw_bond(Origin::signed(COMPANY), 0), "a verdict can still be appealed");
+            System::set_block_number(5);
+            assert_ok!(DataSpace::withdraw_bond(Origin::signed(COMPANY), 0));
+            assert_eq!(Balances::free_balance(&PERSON), person_free + 60);
+            assert_eq!(DataSpace::get_dispute(0).unwrap().verdict, Some((PERSON, 0)));
+            assert_noop!(DataSpace::decide_appeal(council_majority(), 0, true), "appeal was already decided");
+        });
+    }
+
+    #[test]
+    fn locked_companies_can_still_appeal() {
+        with_externalities(&mut new_test_ext(), || {
+            let mut parameters: MarketParameters<u64> = Default::default();
+            parameters.company_lock_period = 20;
+            assert_ok!(DataSpace::set_parameters(Origin::ROOT, parameters));
+            assert_noop!(DataSpace::set_fees(Origin::ROOT, 1, 2, 0, 60, 60, 20), "appeal bond must exceed the dispute penalty");
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 100, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            assert_ok!(DataSpace::buy_data(Origin::signed(COMPANY), PERSON, 0));
+            assert_ok!(DataSpace::tip_off_data(Origin::signed(COMPANY), 0));
+            <DataSpace as OnFinalize<u64>>::on_finalize(3);
+            assert_eq!(DataSpace::get_dispute(0).unwrap().verdict, Some((PERSON, 0)));
+
+            // the lock outlasts the appeal period, it only stops transfers
+            System::set_block_number(4);
+            assert!(Balances::transfer(Origin::signed(COMPANY), 3, 10).is_err());
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"more".to_vec(), b"food".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::appeal(Origin::signed(COMPANY), 0));
+            assert_eq!(Balances::reserved_balance(&COMPANY), 60);
+        });
+    }
+
+    #[test]
+    fn root_changes_market_parameters() {
+        with_externalities(&mut new_test_ext(), || {
+            let parameters = MarketParameters { person_lock_period: 4, company_lock_period: 8, min_data_len: 5, reject_order_content: false, require_company_approval: true,
+                max_answer_period: 100, aggregate_report_period: 10, min_contributors: 1, max_epsilon_millis: 1000 };
+            assert!(DataSpace::set_parameters(Origin::signed(COMPANY), parameters.clone()).is_err());
+            assert_ok!(DataSpace::set_parameters(Origin::ROOT, parameters));
+            assert!(!DataSpace::validate_data(b"rice".to_vec(), b"meals".to_vec()));
+            assert!(DataSpace::validate_data(b"meals".to_vec(), b"meals".to_vec()));
+
+            assert_noop!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]),
+                "company is not approved");
+            assert!(DataSpace::set_company_approval(technical_majority(), COMPANY, true).is_err());
+            assert_ok!(DataSpace::set_company_approval(council_majority(), COMPANY, true));
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::set_company_approval(Origin::ROOT, 3, true));
+
+            assert!(DataSpace::set_size_limits(council_majority(), 4, 16, 40).is_err());
+            assert_ok!(DataSpace::set_size_limits(technical_majority(), 4, 16, 40));
+            assert_noop!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"grain".to_vec(), COMPANY, 0), "data is too large");
+        });
+    }
+
+    #[test]
+    fn genesis_seeds_the_marketplace() {
+        let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+        t.extend(GenesisConfig::<Test>{
+            max_data_size: 16,
+            abandon_period: 10,
+            parameters: Default::default(),
+            companies: vec![3],
+            orders: vec![(COMPANY, b"food".to_vec(), b"meals".to_vec(), 10, b"food".to_vec())],
+            uploads: vec![(PERSON, COMPANY, 0, b"d".to_vec(), b"rice".to_vec())],
+            ..Default::default()
+        }.build_storage().unwrap().0);
+        with_externalities(&mut t.into(), || {
+            assert!(<Company<Test>>::exists(&3));
+            assert_eq!(DataSpace::list_orders(0, 10), vec![(COMPANY, 0)]);
+            assert_eq!(DataSpace::uploader_count(&(COMPANY, 0)), 1);
+            assert_eq!(DataSpace::uploader_at(&(COMPANY, 0, 0)), Some(PERSON));
+            assert_eq!(DataSpace::get_from_chain(0), b"rice".to_vec());
+            assert_eq!(DataSpace::get_data_info(0).unwrap().expires_at, 10);
+        });
+    }
+
+    #[test]
+    fn abandoned_data_is_collected() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_ok!(DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), b"rice".to_vec(), COMPANY, 0));
+            <DataSpace as OnFinalize<u64>>::on_finalize(9);
+            assert_eq!(DataSpace::get_data(&PERSON).len(), 1);
+            <DataSpace as OnFinalize<u64>>::on_finalize(10);
+            assert!(DataSpace::get_data(&PERSON).is_empty());
+            assert_eq!(DataSpace::get_content(0), Vec::<u8>::new());
+            assert_eq!(Balances::reserved_balance(&PERSON), 0);
+        });
+    }
+
+    #[test]
+    fn finalize_carries_over_what_exceeds_the_block_limit() {
+        with_externalities(&mut new_test_ext(), || {
+            // keys without a Data entry, collecting them does nothing
+            <Expiring<Test>>::insert(5, (100..170).collect::<Vec<u64>>());
+            <Expiring<Test>>::insert(6, vec![7]);
+            <DataSpace as OnFinalize<u64>>::on_finalize(5);
+            let carried = DataSpace::expiring_at(6);
+            assert_eq!(carried.len(), 70 - MAX_FINALIZE_ITEMS + 1);
+            assert_eq!(carried[0], 100 + MAX_FINALIZE_ITEMS as u64);
+            assert_eq!(carried.last(), Some(&7));
+        });
+    }
+
+    #[test]
+    fn oversized_payloads_are_rejected() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(
+                DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), vec![1; 17], 10, vec![], vec![], b"food".to_vec(), vec![]),
+                "order content is too large"
+            );
+            assert_ok!(DataSpace::publish_order(Origin::signed(COMPANY), b"food".to_vec(), b"meals".to_vec(), 10, vec![], vec![], b"food".to_vec(), vec![]));
+            assert_noop!(
+                DataSpace::upload_data(Origin::signed(PERSON), b"d".to_vec(), vec![1; 17], COMPANY, 0),
+                "data is too large"
+            );
+            assert_noop!(
+                DataSpace::upload_data(Origin::signed(PERSON), vec![1; 65], b"rice".to_vec(), COMPANY, 0),
+                "data name is too long"
+            );
+        });
+    }
+}
@@ -23,6 +23,7 @@ use client::{
 use version::RuntimeVersion;
 #[cfg(feature = "std")]
 use version::NativeVersion;
+use council::{motions as council_motions, seats as council_seats, voting as council_voting};
 
 // A few exports that help ease life for downstream crates.
 #[cfg(any(feature = "std", test))]
@@ -30,6 +31,7 @@ pub use runtime_primitives::BuildStorage;
 pub use consensus::Call as ConsensusCall;
 pub use timestamp::Call as TimestampCall;
 pub use balances::Call as BalancesCall;
+pub use data_space::Call as DataspaceCall;
 pub use runtime_primitives::{Permill, Perbill};
 pub use timestamp::BlockPeriod;
 pub use support::{StorageValue, construct_runtime};
@@ -55,8 +57,17 @@ pub type BlockNumber = u64;
 /// Index of an account's extrinsic in the chain.
 pub type Nonce = u64;
 
+/// Balance of an account.
+pub type Balance = u128;
+
 /// Used for the module data_space in `./data_space.rs`
-mod data_space;
+pub mod data_space;
+
+/// Used for the module attestation in `./attestation.rs`
+pub mod attestation;
+
+/// Used for the module technical_committee in `./technical_committee.rs`
+pub mod technical_committee;
 
 /// Opaque types. These are used by the CLI to instantiate machinery that don't need to know
 /// the specifics of the runtime. They can then be made to be agnostic over specific formats
@@ -168,7 +179,7 @@ impl timestamp::Trait for Runtime {
 
 impl balances::Trait for Runtime {
 	/// The type for recording an account's balance.
-	type Balance = u128;
+	type Balance = Balance;
 	/// What to do if an account's free balance gets zeroed.
 	type OnFreeBalanceZero = ();
 	/// What to do if a new account is created.
@@ -181,18 +192,91 @@ impl balances::Trait for Runtime {
 	type TransferPayment = ();
 }
 
+/// Sudo stays until the council is running, then a referendum can upgrade the runtime without it.
 impl sudo::Trait for Runtime {
 	/// The uniquitous event type.
 	type Event = Event;
 	type Proposal = Call;
 }
 
+/// Referenda dispatch with the root origin, so they can call every root-only marketplace setter.
+impl democracy::Trait for Runtime {
+	type Currency = Balances;
+	type Proposal = Call;
+	type Event = Event;
+}
+
+impl council::Trait for Runtime {
+	type Event = Event;
+	type BadPresentation = ();
+	type BadReaper = ();
+}
+
+/// The council puts proposals to a referendum by voting on them.
+impl council::voting::Trait for Runtime {
+	type Event = Event;
+}
+
+impl council::motions::Trait for Runtime {
+	type Origin = Origin;
+	type Proposal = Call;
+	type Event = Event;
+}
+
+/// The technical committee decides the size limits and periods of the marketplace by its own motions.
+impl technical_committee::Trait for Runtime {
+	type Origin = Origin;
+	type Proposal = Call;
+	type Event = Event;
+}
+
+
+impl attestation::Trait for Runtime {
+	type Event = Event;
+}
 
 //use support::traits::Currency;
 /// Used for the module template in `./data_space.rs`
 impl data_space::Trait for Runtime {
 	type Event = Event;
 	type Currency = Balances;
+	type Reporters = AuthorityReporters;
+	type Signature = AccountSignature;
+	type Governance = MotionOrigins;
+}
+
+/// Company approvals, referred disputes and appeals are decided by motions passed by a majority of the elected
+/// council, the technical settings by motions of the technical committee. Root (referenda, and sudo during the
+/// migration) can do both.
+pub struct MotionOrigins;
+
+impl data_space::Governance<Origin> for MotionOrigins {
+	fn ensure_majority(origin: Origin) -> Result<(), &'static str> {
+		if system::ensure_root(origin.clone()).is_ok() {
+			return Ok(());
+		}
+		let members = council_seats::Module::<Runtime>::active_council().len() as u32;
+		council_motions::ensure_council_members(origin, members / 2 + 1).map(|_| ())
+	}
+
+	fn ensure_technical(origin: Origin) -> Result<(), &'static str> {
+		if system::ensure_root(origin.clone()).is_ok() {
+			return Ok(());
+		}
+		let members = TechnicalCommittee::members().len() as u32;
+		technical_committee::ensure_members(origin, members / 2 + 1).map(|_| ())
+	}
+}
+
+/// Validation and aggregate reports are signed by the block authorities.
+pub struct AuthorityReporters;
+
+impl data_space::Reporters for AuthorityReporters {
+	type Id = AuthorityId;
+	type Signature = AuthoritySignature;
+	fn is_reporter(who: &AuthorityId) -> bool {
+		Consensus::authorities().contains(who)
+	}
 }
 
 construct_runtime!(
@@ -208,8 +292,15 @@ construct_runtime!(
 		Indices: indices,
 		Balances: balances,
 		Sudo: sudo,
+		Democracy: democracy,
+		Council: council::{Module, Call, Storage, Event<T>},
+		CouncilVoting: council_voting,
+		CouncilMotions: council_motions::{Module, Call, Storage, Event<T>, Origin},
+		CouncilSeats: council_seats::{Config<T>},
+		TechnicalCommittee: technical_committee::{Module, Call, Storage, Event<T>, Origin, Config<T>},
+		Attestation: attestation::{Module, Call, Storage, Event<T>},
 		// Used for the module data_space in `./data_space.rs`
-		DataspaceModule: data_space::{Module, Call, Storage, Event<T>},
+		DataspaceModule: data_space::{Module, Call, Storage, Event<T>, Config<T>},
 	}
 );
 
@@ -280,7 +371,11 @@ impl_runtime_apis! {
 
 	impl runtime_api::TaggedTransactionQueue<Block> for Runtime {
 		fn validate_transaction(tx: <Block as BlockT>::Extrinsic) -> TransactionValidity {
-			Executive::validate_transaction(tx)
+			// the executive refuses unsigned extrinsics, the data space reports are checked by their module instead
+			match (&tx.signature, &tx.function) {
+				(None, Call::DataspaceModule(call)) => DataspaceModule::validate_unsigned(call),
+				_ => Executive::validate_transaction(tx),
+			}
 		}
 	}
 
@@ -301,4 +396,46 @@ impl_runtime_apis! {
 			Consensus::authorities()
 		}
 	}
+
+	impl data_space::DataSpaceApi<Block, AccountId, Balance, Hash, BlockNumber> for Runtime {
+		fn current_price(company: AccountId, person: AccountId, order_id: usize) -> Option<Balance> {
+			DataspaceModule::current_price(&company, &person, order_id)
+		}
+
+		fn flagged_uploads(company: AccountId, order_id: usize) -> Vec<(AccountId, usize)> {
+			DataspaceModule::flagged_uploads(&(company, order_id))
+		}
+
+		fn eligible_orders(person: AccountId, companies: Vec<AccountId>) -> Vec<(AccountId, usize)> {
+			DataspaceModule::eligible_orders(&person, companies)
+		}
+
+		fn list_orders(start: u32, count: u32) -> Vec<(AccountId, usize)> {
+			DataspaceModule::list_orders(start, count)
+		}
+
+		fn list_orders_in_category(category: Vec<u8>, start: u32, count: u32) -> Vec<(AccountId, usize)> {
+			DataspaceModule::list_orders_in_category(category, start, count)
+		}
+
+		fn list_orders_with_tag(tag: Vec<u8>, start: u32, count: u32) -> Vec<(AccountId, usize)> {
+			DataspaceModule::list_orders_with_tag(tag, start, count)
+		}
+
+		fn pending_checks(max: u32) -> Vec<data_space::UploadCheck<AccountId, Hash>> {
+			DataspaceModule::pending_checks(max)
+		}
+
+		fn aggregate_inputs() -> Vec<data_space::AggregateInput<AccountId>> {
+			DataspaceModule::aggregate_inputs()
+		}
+
+		fn is_aggregation_key(key: [u8; 32]) -> bool {
+			DataspaceModule::aggregation_key_owner(&key).is_some()
+		}
+
+		fn dispute(deal_id: u64) -> Option<data_space::Dispute<AccountId, Hash, BlockNumber>> {
+			DataspaceModule::get_dispute(deal_id)
+		}
+	}
 }
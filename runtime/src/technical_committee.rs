@@ -0,0 +1,312 @@
+use rstd::prelude::*;
+use rstd::result;
+use parity_codec::{Decode, Encode};
+use support::{
+    decl_event, decl_module, decl_storage, dispatch::{Dispatchable, Result}, ensure, Parameter, StorageMap, StorageValue};
+use runtime_primitives::traits::{Hash, Zero};
+use system::{ensure_root, ensure_signed};
+
+// A small body of experts deciding the technical settings of the marketplace. Members propose calls and vote
+// on them like council motions, a call approved by threshold members is dispatched with the Members origin.
+pub trait Trait: system::Trait {
+    type Origin: From<RawOrigin<Self::AccountId>>;
+    type Proposal: Parameter + Dispatchable<Origin = <Self as Trait>::Origin>;
+    type Event: From<Event<Self>> + Into<<Self as system::Trait>::Event>;
+}
+
+// the most proposals open at once
+pub const MAX_PROPOSALS: usize = 16;
+
+// The origin of a call approved by the committee, with how many members approved it.
+#[derive(PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum RawOrigin<AccountId> {
+    Members(u32),
+    _Phantom(rstd::marker::PhantomData<AccountId>),
+}
+
+pub type Origin<T> = RawOrigin<<T as system::Trait>::AccountId>;
+
+// The votes on an open proposal.
+#[derive(Encode, Decode, Clone, PartialEq)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub struct Votes<AccountId, BlockNumber> {
+    index: u32,
+    threshold: u32,
+    ayes: Vec<AccountId>,
+    nays: Vec<AccountId>,
+    // the block from which the proposal can no longer pass and anyone can close it
+    end: BlockNumber,
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as TechnicalCommittee {
+        // the members of the committee, changed by root
+        pub Members get(members) config(): Vec<T::AccountId>;
+        // hashes of the open proposals
+        pub Proposals get(proposals): Vec<T::Hash>;
+        pub ProposalOf get(proposal_of): map T::Hash => Option<<T as Trait>::Proposal>;
+        pub Voting get(voting): map T::Hash => Option<Votes<T::AccountId, T::BlockNumber>>;
+        // how many proposals were made, the index of the next one
+        pub ProposalCount get(proposal_count): u32;
+        // blocks a proposal stays open
+        pub VotingPeriod get(voting_period) config(): T::BlockNumber;
+    }
+}
+
+decl_event!(
+    pub enum Event<T> where <T as system::Trait>::Hash, <T as system::Trait>::AccountId {
+        // member, proposal index, proposal hash, threshold
+        Proposed(AccountId, u32, Hash, u32),
+        // member, proposal hash, approve, ayes, nays
+        Voted(AccountId, Hash, bool, u32, u32),
+        // proposal hash, whether the call succeeded
+        Executed(Hash, bool),
+        // proposal hash, too many members voted against it
+        Disapproved(Hash),
+        // proposal hash, it was not approved before its voting period ended
+        Expired(Hash),
+        // the members after the change
+        MembersChanged(Vec<AccountId>),
+    }
+);
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: <T as system::Trait>::Origin {
+        fn deposit_event<T>() = default;
+
+        // Root (a referendum, or sudo during the migration) sets the members. Open proposals stay open, but only
+        // the votes of current members count.
+        fn set_members(origin, members: Vec<T::AccountId>) -> Result {
+            ensure_root(origin)?;
+            <Members<T>>::put(&members);
+            Self::deposit_event(RawEvent::MembersChanged(members));
+            Ok(())
+        }
+
+        // A member proposes a call, it is dispatched once threshold members approved it.
+        // The proposer approves it, so a threshold of one dispatches it right away.
+        fn propose(origin, threshold: u32, proposal: Box<<T as Trait>::Proposal>) -> Result {
+            let who = ensure_signed(origin)?;
+            ensure!(Self::is_member(&who), "only members can propose");
+            ensure!(threshold > 0, "threshold is zero");
+            let hash = T::Hashing::hash_of(&proposal);
+            ensure!(!<ProposalOf<T>>::exists(hash), "proposal is already open");
+
+            if threshold == 1 {
+                let ok = proposal.dispatch(RawOrigin::Members(1).into()).is_ok();
+                Self::deposit_event(RawEvent::Executed(hash, ok));
+                return Ok(());
+            }
+            ensure!(!Self::voting_period().is_zero(), "proposals can't be voted on");
+            let mut proposals = Self::proposals();
+            ensure!(proposals.len() < MAX_PROPOSALS, "too many open proposals");
+            let index = Self::proposal_count();
+            <ProposalCount<T>>::put(index + 1);
+            proposals.push(hash);
+            <Proposals<T>>::put(proposals);
+            <ProposalOf<T>>::insert(hash, *proposal);
+            let end = <system::Module<T>>::block_number() + Self::voting_period();
+            <Voting<T>>::insert(hash, Votes { index: index, threshold: threshold, ayes: vec![who.clone()], nays: Vec::new(), end: end });
+            Self::deposit_event(RawEvent::Proposed(who, index, hash, threshold));
+            Ok(())
+        }
+
+        // Members vote on an open proposal, they can change their vote until it is closed.
+        // It is closed as soon as threshold members approve it, or too few members are left to approve it.
+        fn vote(origin, proposal: T::Hash, index: u32, approve: bool) -> Result {
+            let who = ensure_signed(origin)?;
+            ensure!(Self::is_member(&who), "only members can vote");
+            let mut voting = Self::voting(&proposal).ok_or("no such proposal")?;
+            ensure!(voting.index == index, "proposal index does not match");
+            ensure!(<system::Module<T>>::block_number() < voting.end, "voting on the proposal is over");
+
+            // members who left since they voted no longer count
+            let members = Self::members();
+            voting.ayes.retain(|m| m != &who && members.contains(m));
+            voting.nays.retain(|m| m != &who && members.contains(m));
+            if approve {
+                voting.ayes.push(who.clone());
+            } else {
+                voting.nays.push(who.clone());
+            }
+            let (ayes, nays) = (voting.ayes.len() as u32, voting.nays.len() as u32);
+            Self::deposit_event(RawEvent::Voted(who, proposal, approve, ayes, nays));
+
+            let members = members.len() as u32;
+            if ayes >= voting.threshold {
+                if let Some(call) = Self::close(proposal) {
+                    let ok = call.dispatch(RawOrigin::Members(voting.threshold).into()).is_ok();
+                    Self::deposit_event(RawEvent::Executed(proposal, ok));
+                }
+            } else if members.saturating_sub(nays) < voting.threshold {
+                Self::close(proposal);
+                Self::deposit_event(RawEvent::Disapproved(proposal));
+            } else {
+                <Voting<T>>::insert(proposal, voting);
+            }
+            Ok(())
+        }
+
+        // Anyone can close a proposal whose voting period ended without approving it, which frees its slot.
+        fn close_expired(origin, proposal: T::Hash) -> Result {
+            ensure_signed(origin)?;
+            let voting = Self::voting(&proposal).ok_or("no such proposal")?;
+            ensure!(<system::Module<T>>::block_number() >= voting.end, "voting on the proposal is not over");
+            Self::close(proposal);
+            Self::deposit_event(RawEvent::Expired(proposal));
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    pub fn is_member(who: &T::AccountId) -> bool {
+        Self::members().contains(who)
+    }
+
+    // remove an open proposal, returns its call
+    fn close(proposal: T::Hash) -> Option<<T as Trait>::Proposal> {
+        <Proposals<T>>::mutate(|proposals| proposals.retain(|h| h != &proposal));
+        <Voting<T>>::remove(proposal);
+        <ProposalOf<T>>::take(proposal)
+    }
+}
+
+// Ok if the origin is a call approved by at least n committee members, with how many approved it.
+pub fn ensure_members<OuterOrigin, AccountId>(o: OuterOrigin, n: u32) -> result::Result<u32, &'static str>
+    where OuterOrigin: Into<result::Result<RawOrigin<AccountId>, OuterOrigin>>
+{
+    match o.into() {
+        Ok(RawOrigin::Members(x)) if x >= n => Ok(x),
+        _ => Err("bad origin: expected a threshold of technical committee members"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::technical_committee;
+
+    use runtime_io::with_externalities;
+    use primitives::{H256, Blake2Hasher};
+    use support::{impl_outer_origin, assert_ok, assert_noop};
+    use runtime_primitives::{
+        BuildStorage,
+        traits::{BlakeTwo256, IdentityLookup},
+        testing::{Digest, DigestItem, Header}
+    };
+
+    impl_outer_origin! {
+        pub enum Origin for Test {
+            technical_committee<T>
+        }
+    }
+
+    #[derive(Clone, Eq, PartialEq)]
+    pub struct Test;
+    impl system::Trait for Test {
+        type Origin = Origin;
+        type Index = u64;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type Hashing = BlakeTwo256;
+        type Digest = Digest;
+        type AccountId = u64;
+        type Lookup = IdentityLookup<Self::AccountId>;
+        type Header = Header;
+        type Event = ();
+        type Log = DigestItem;
+    }
+    impl Trait for Test {
+        type Origin = Origin;
+        type Proposal = Call<Test>;
+        type Event = ();
+    }
+
+    type Committee = Module<Test>;
+    type System = system::Module<Test>;
+
+    fn new_test_ext() -> runtime_io::TestExternalities<Blake2Hasher> {
+        let mut t = system::GenesisConfig::<Test>::default().build_storage().unwrap().0;
+        t.extend(GenesisConfig::<Test>{
+            members: vec![1, 2, 3],
+            voting_period: 10,
+        }.build_storage().unwrap().0);
+        t.into()
+    }
+
+    // a proposal only root could dispatch, it closes whatever the call returns
+    fn proposal() -> Box<Call<Test>> {
+        Box::new(Call::set_members(vec![1, 2]))
+    }
+
+    #[test]
+    fn approved_proposals_are_dispatched_with_the_members_origin() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_noop!(Committee::propose(Origin::signed(4), 2, proposal()), "only members can propose");
+            assert_ok!(Committee::propose(Origin::signed(1), 2, proposal()));
+            let hash = Committee::proposals()[0];
+            assert_noop!(Committee::vote(Origin::signed(2), hash, 1, true), "proposal index does not match");
+            assert_ok!(Committee::vote(Origin::signed(2), hash, 0, true));
+            // the committee is not root, so the call fails, but the proposal is closed
+            assert!(Committee::proposals().is_empty());
+            assert!(Committee::proposal_of(hash).is_none());
+            assert_eq!(Committee::members(), vec![1, 2, 3]);
+
+            assert_eq!(ensure_members(Origin::from(RawOrigin::Members(2)), 2), Ok(2));
+            assert!(ensure_members(Origin::from(RawOrigin::Members(1)), 2).is_err());
+            assert!(ensure_members(Origin::signed(1), 1).is_err());
+        });
+    }
+
+    #[test]
+    fn proposals_close_when_they_cannot_pass() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Committee::propose(Origin::signed(1), 3, proposal()));
+            let hash = Committee::proposals()[0];
+            assert_ok!(Committee::vote(Origin::signed(2), hash, 0, true));
+            assert_eq!(Committee::voting(hash).unwrap().ayes, vec![1, 2]);
+            // a member can change their vote, one nay leaves too few members to reach three ayes
+            assert_ok!(Committee::vote(Origin::signed(2), hash, 0, false));
+            assert!(Committee::proposals().is_empty());
+            assert_noop!(Committee::vote(Origin::signed(3), hash, 0, true), "no such proposal");
+
+            assert_ok!(Committee::set_members(Origin::ROOT, vec![4]));
+            assert_noop!(Committee::propose(Origin::signed(1), 1, proposal()), "only members can propose");
+        });
+    }
+
+    #[test]
+    fn votes_of_former_members_do_not_count() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Committee::propose(Origin::signed(1), 2, proposal()));
+            let hash = Committee::proposals()[0];
+            assert_ok!(Committee::set_members(Origin::ROOT, vec![2, 3, 4]));
+            // the aye of member 1 is gone, one aye is not enough
+            assert_ok!(Committee::vote(Origin::signed(2), hash, 0, true));
+            assert_eq!(Committee::voting(hash).unwrap().ayes, vec![2]);
+            assert_ok!(Committee::vote(Origin::signed(3), hash, 0, true));
+            assert!(Committee::proposals().is_empty());
+        });
+    }
+
+    #[test]
+    fn expired_proposals_are_closed() {
+        with_externalities(&mut new_test_ext(), || {
+            assert_ok!(Committee::propose(Origin::signed(1), 3, proposal()));
+            let hash = Committee::proposals()[0];
+            assert_noop!(Committee::close_expired(Origin::signed(4), hash), "voting on the proposal is not over");
+
+            System::set_block_number(10);
+            assert_noop!(Committee::vote(Origin::signed(2), hash, 0, true), "voting on the proposal is over");
+            assert_ok!(Committee::close_expired(Origin::signed(4), hash));
+            assert!(Committee::proposals().is_empty());
+            assert!(Committee::proposal_of(hash).is_none());
+            assert!(Committee::voting(hash).is_none());
+            // the same call can be proposed again
+            assert_ok!(Committee::propose(Origin::signed(1), 3, proposal()));
+            assert_eq!(Committee::voting(hash).unwrap().end, 20);
+        });
+    }
+}
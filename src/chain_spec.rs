@@ -1,8 +1,14 @@
 use data_space_runtime::{
-    AccountId, BalancesConfig, ConsensusConfig, GenesisConfig, IndicesConfig, SudoConfig,
+    AccountId, Balance, BalancesConfig, ConsensusConfig, CouncilSeatsConfig, CouncilVotingConfig,
+    DataspaceModuleConfig, DemocracyConfig, GenesisConfig, IndicesConfig, SudoConfig, TechnicalCommitteeConfig,
     TimestampConfig,
 };
-use primitives::{ed25519, sr25519, Pair};
+use data_space_runtime::data_space::MarketParameters;
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use primitives::{crypto::Ss58Codec, ed25519, sr25519, Pair};
+use serde::Deserialize;
+use std::{env, fs, iter, path::Path};
 use substrate_service;
 
 use ed25519::Public as AuthorityId;
@@ -10,6 +16,14 @@ use ed25519::Public as AuthorityId;
 // Note this is the URL for the telemetry server
 //const STAGING_TELEMETRY_URL: &str = "wss://telemetry.polkadot.io/submit/";
 
+/// The environment variable naming the file with the keys of the staging network.
+pub const STAGING_KEYS_VAR: &str = "DATA_SPACE_STAGING_KEYS";
+
+lazy_static! {
+    // the description `described_genesis` builds from, `ChainSpec::from_genesis` only takes a plain function
+    static ref DESCRIBED_NETWORK: Mutex<Option<NetworkDescription>> = Mutex::new(None);
+}
+
 /// Specialized `ChainSpec`. This is a specialization of the general Substrate ChainSpec type.
 pub type ChainSpec = substrate_service::ChainSpec<GenesisConfig>;
 
@@ -22,6 +36,206 @@ pub enum Alternative {
     Development,
     /// Whatever the current runtime is, with simple Alice/Bob auths.
     LocalTestnet,
+    /// The shared staging network, described by `res/staging.toml` and the keys file named by `DATA_SPACE_STAGING_KEYS`.
+    StagingTestnet,
+}
+
+/// A network described in TOML: its validators, governance, balances, boot nodes and the marketplace it starts with.
+/// Keys are SS58 addresses, or `//Seed` derivations for test networks.
+#[derive(Deserialize)]
+pub struct NetworkDescription {
+    name: String,
+    id: String,
+    #[serde(default)]
+    boot_nodes: Vec<String>,
+    authorities: Vec<String>,
+    root: String,
+    /// The council until the first election, the root key alone if empty.
+    #[serde(default)]
+    council: Vec<String>,
+    #[serde(default)]
+    technical_committee: Vec<String>,
+    #[serde(default)]
+    balances: Vec<BalanceDescription>,
+    #[serde(default)]
+    companies: Vec<CompanyDescription>,
+}
+
+/// The initial free balance of an account.
+#[derive(Deserialize)]
+pub struct BalanceDescription {
+    account: String,
+    balance: u64,
+}
+
+/// A company registered at genesis, with the orders it publishes.
+#[derive(Deserialize)]
+pub struct CompanyDescription {
+    account: String,
+    #[serde(default)]
+    orders: Vec<OrderDescription>,
+}
+
+/// The public part of the staging network, its accounts are kept out of the repository.
+#[derive(Deserialize)]
+struct StagingNetwork {
+    name: String,
+    id: String,
+    #[serde(default)]
+    boot_nodes: Vec<String>,
+}
+
+/// The keys of the staging network, as SS58 addresses.
+#[derive(Deserialize)]
+struct StagingKeys {
+    authorities: Vec<String>,
+    root: String,
+    #[serde(default)]
+    council: Vec<String>,
+    #[serde(default)]
+    technical_committee: Vec<String>,
+    #[serde(default)]
+    balances: Vec<BalanceDescription>,
+    #[serde(default)]
+    companies: Vec<CompanyDescription>,
+}
+
+/// An order published at genesis.
+#[derive(Deserialize)]
+pub struct OrderDescription {
+    name: String,
+    content: String,
+    price: u64,
+    #[serde(default)]
+    category: String,
+}
+
+impl NetworkDescription {
+    /// Parse a description from TOML.
+    pub fn from_toml(toml: &str) -> Result<Self, String> {
+        toml::from_str(toml).map_err(|e| format!("Invalid network description: {}", e))
+    }
+
+    /// Build the genesis of the described network.
+    pub fn genesis(&self) -> Result<GenesisConfig, String> {
+        let authorities = self.authorities.iter()
+            .map(|key| parse_authority(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let balances = self.balances.iter()
+            .map(|b| Ok((parse_account(&b.account)?, Balance::from(b.balance))))
+            .collect::<Result<Vec<_>, String>>()?;
+        let root = parse_account(&self.root)?;
+        let mut council = self.council.iter()
+            .map(|key| parse_account(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        if council.is_empty() {
+            council.push(root.clone());
+        }
+        let technical_committee = self.technical_committee.iter()
+            .map(|key| parse_account(key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut companies = Vec::new();
+        let mut orders = Vec::new();
+        for company in &self.companies {
+            let account = parse_account(&company.account)?;
+            companies.push(account.clone());
+            orders.extend(company.orders.iter().map(|order| (
+                account.clone(),
+                order.name.as_bytes().to_vec(),
+                order.content.as_bytes().to_vec(),
+                Balance::from(order.price),
+                order.category.as_bytes().to_vec(),
+            )));
+        }
+        Ok(network_genesis(authorities, balances, root, council, technical_committee, companies, orders, vec![]))
+    }
+
+    /// The chain spec of the described network.
+    pub fn chain_spec(self) -> Result<ChainSpec, String> {
+        // fail here rather than in the genesis constructor
+        self.genesis()?;
+        let spec = ChainSpec::from_genesis(
+            &self.name,
+            &self.id,
+            described_genesis,
+            self.boot_nodes.clone(),
+            None,
+            None,
+            None,
+            None,
+        );
+        *DESCRIBED_NETWORK.lock() = Some(self);
+        Ok(spec)
+    }
+
+    // the first key given as a `//Seed` derivation, anyone can derive its secret
+    fn seed(&self) -> Option<&String> {
+        self.authorities.iter()
+            .chain(iter::once(&self.root))
+            .chain(self.council.iter())
+            .chain(self.technical_committee.iter())
+            .chain(self.balances.iter().map(|b| &b.account))
+            .chain(self.companies.iter().map(|c| &c.account))
+            .find(|key| key.starts_with("//"))
+    }
+}
+
+fn described_genesis() -> GenesisConfig {
+    DESCRIBED_NETWORK.lock()
+        .as_ref()
+        .expect("set before the chain spec is returned; qed")
+        .genesis()
+        .expect("checked before the chain spec is returned; qed")
+}
+
+fn parse_authority(key: &str) -> Result<AuthorityId, String> {
+    if key.starts_with("//") {
+        ed25519::Pair::from_string(key, None).map(|pair| pair.public()).map_err(|_| format!("Invalid seed {}", key))
+    } else {
+        AuthorityId::from_ss58check(key).map_err(|_| format!("Invalid authority {}", key))
+    }
+}
+
+fn parse_account(key: &str) -> Result<AccountId, String> {
+    if key.starts_with("//") {
+        sr25519::Pair::from_string(key, None).map(|pair| pair.public()).map_err(|_| format!("Invalid seed {}", key))
+    } else {
+        AccountId::from_ss58check(key).map_err(|_| format!("Invalid account {}", key))
+    }
+}
+
+fn staging_description() -> Result<NetworkDescription, String> {
+    let network: StagingNetwork = toml::from_str(include_str!("../res/staging.toml"))
+        .expect("the bundled staging description is valid; qed");
+    let path = env::var(STAGING_KEYS_VAR)
+        .map_err(|_| format!("Set {} to the file with the keys of the staging network", STAGING_KEYS_VAR))?;
+    let keys = fs::read_to_string(&path).map_err(|e| format!("Error reading {}: {}", path, e))?;
+    let keys: StagingKeys = toml::from_str(&keys).map_err(|e| format!("Invalid staging keys in {}: {}", path, e))?;
+    let description = NetworkDescription {
+        name: network.name,
+        id: network.id,
+        boot_nodes: network.boot_nodes,
+        authorities: keys.authorities,
+        root: keys.root,
+        council: keys.council,
+        technical_committee: keys.technical_committee,
+        balances: keys.balances,
+        companies: keys.companies,
+    };
+    if let Some(seed) = description.seed() {
+        return Err(format!("The staging network only takes SS58 addresses, found {}", seed));
+    }
+    Ok(description)
+}
+
+/// Load a chain spec from a file: a TOML network description, or a plain or raw JSON spec.
+pub fn load_file(path: &Path) -> Result<ChainSpec, String> {
+    if path.extension().map_or(false, |ext| ext == "toml") {
+        let toml = fs::read_to_string(path).map_err(|e| format!("Error reading {}: {}", path.display(), e))?;
+        NetworkDescription::from_toml(&toml)?.chain_spec()
+    } else {
+        ChainSpec::from_json_file(path.to_path_buf())
+    }
 }
 
 fn authority_key(s: &str) -> AuthorityId {
@@ -46,7 +260,7 @@ impl Alternative {
                 || {
                     testnet_genesis(
                         vec![authority_key("Alice")],
-                        vec![account_key("Alice")],
+                        vec![account_key("Alice"), account_key("Bob"), account_key("Charlie")],
                         account_key("Alice"),
                     )
                 },
@@ -79,6 +293,7 @@ impl Alternative {
                 None,
                 None,
             ),
+            Alternative::StagingTestnet => staging_description()?.chain_spec()?,
         })
     }
 
@@ -86,6 +301,7 @@ impl Alternative {
         match s {
             "dev" => Some(Alternative::Development),
             "" | "local" => Some(Alternative::LocalTestnet),
+            "staging" => Some(Alternative::StagingTestnet),
             _ => None,
         }
     }
@@ -95,6 +311,38 @@ fn testnet_genesis(
     initial_authorities: Vec<AuthorityId>,
     endowed_accounts: Vec<AccountId>,
     root_key: AccountId,
+) -> GenesisConfig {
+    // Alice, Bob and Charlie sit on the council and the technical committee.
+    // Alice runs a small survey marketplace which Bob and Charlie already answered.
+    let members = vec![account_key("Alice"), account_key("Bob"), account_key("Charlie")];
+    network_genesis(
+        initial_authorities,
+        endowed_accounts.into_iter().map(|k| (k, 1 << 60)).collect(),
+        root_key,
+        members.clone(),
+        members,
+        vec![account_key("Alice")],
+        vec![
+            (account_key("Alice"), b"Daily meals".to_vec(), b"What did you eat today?".to_vec(), 100, b"food".to_vec()),
+            (account_key("Alice"), b"Commute".to_vec(), b"How do you get to work?".to_vec(), 150, b"mobility".to_vec()),
+        ],
+        vec![
+            (account_key("Bob"), account_key("Alice"), 0, b"meals".to_vec(), b"rice and beans".to_vec()),
+            (account_key("Charlie"), account_key("Alice"), 0, b"meals".to_vec(), b"noodle soup".to_vec()),
+            (account_key("Bob"), account_key("Alice"), 1, b"commute".to_vec(), b"bike, 20 minutes".to_vec()),
+        ],
+    )
+}
+
+fn network_genesis(
+    initial_authorities: Vec<AuthorityId>,
+    balances: Vec<(AccountId, Balance)>,
+    root_key: AccountId,
+    council: Vec<AccountId>,
+    technical_committee: Vec<AccountId>,
+    companies: Vec<AccountId>,
+    orders: Vec<(AccountId, Vec<u8>, Vec<u8>, Balance, Vec<u8>)>,
+    uploads: Vec<(AccountId, AccountId, usize, Vec<u8>, Vec<u8>)>,
 ) -> GenesisConfig {
     GenesisConfig {
 		consensus: Some(ConsensusConfig {
@@ -106,19 +354,81 @@ fn testnet_genesis(
 			minimum_period: 5, // 10 second block time.
 		}),
 		indices: Some(IndicesConfig {
-			ids: endowed_accounts.clone(),
+			ids: balances.iter().map(|(k, _)| k.clone()).collect(),
 		}),
 		balances: Some(BalancesConfig {
 			transaction_base_fee: 1,
-			transaction_byte_fee: 0,
+			transaction_byte_fee: 1,
 			existential_deposit: 500,
 			transfer_fee: 0,
 			creation_fee: 0,
-			balances: endowed_accounts.iter().cloned().map(|k|(k, 1 << 60)).collect(),
+			balances: balances,
 			vesting: vec![],
 		}),
 		sudo: Some(SudoConfig {
 			key: root_key,
 		}),
+		democracy: Some(DemocracyConfig {
+			launch_period: 24 * 360, // a day.
+			voting_period: 3 * 24 * 360,
+			minimum_deposit: 1_000,
+			public_delay: 0,
+			max_lock_periods: 6,
+		}),
+		// the genesis council sits until the first election, so the council can take over from sudo.
+		council_seats: Some(CouncilSeatsConfig {
+			active_council: council.into_iter().map(|k| (k, 30 * 24 * 360)).collect(),
+			candidacy_bond: 1_000,
+			voter_bond: 100,
+			present_slash_per_voter: 10,
+			carry_count: 4,
+			presentation_duration: 24 * 360,
+			approval_voting_period: 3 * 24 * 360,
+			term_duration: 30 * 24 * 360,
+			desired_seats: 7,
+			inactive_grace_period: 1,
+		}),
+		council_voting: Some(CouncilVotingConfig {
+			cooloff_period: 24 * 360,
+			voting_period: 3 * 24 * 360,
+			enact_delay_period: 0,
+		}),
+		technical_committee: Some(TechnicalCommitteeConfig {
+			members: technical_committee,
+			voting_period: 3 * 24 * 360,
+		}),
+		data_space: Some(DataspaceModuleConfig {
+			max_data_size: 64 * 1024,
+			max_order_content_size: 4 * 1024,
+			byte_fee: 1,
+			deposit_per_byte: 1,
+			abandon_period: 14 * 24 * 360, // two weeks of 10 second blocks.
+			retention_period: 90 * 24 * 360,
+			delivery_period: 3 * 24 * 360,
+			min_company_bond: 1_000,
+			dispute_penalty: 100,
+			evidence_period: 2 * 24 * 360,
+			appeal_period: 3 * 24 * 360,
+			dispute_voting_period: 7 * 24 * 360,
+			appeal_voting_period: 7 * 24 * 360,
+			appeal_bond: 500,
+			min_bid_deposit: 100,
+			parameters: MarketParameters {
+				person_lock_period: 360, // an hour.
+				company_lock_period: 7 * 24 * 360,
+				min_data_len: 1,
+				reject_order_content: true,
+				require_company_approval: false,
+				max_answer_period: 30 * 24 * 360,
+				aggregate_report_period: 24 * 360,
+				min_contributors: 10,
+				max_epsilon_millis: 2000,
+			},
+			max_upload_size: 16 * 1024 * 1024,
+			upload_timeout: 24 * 360, // a day.
+			companies: companies,
+			orders: orders,
+			uploads: uploads,
+		}),
 	}
 }
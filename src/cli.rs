@@ -4,6 +4,7 @@ use futures::{future, sync::oneshot, Future};
 use log::info;
 use std::cell::RefCell;
 use std::ops::Deref;
+use std::path::Path;
 pub use substrate_cli::{error, IntoExit, VersionInfo};
 use substrate_cli::{informant, parse_and_execute, NoCustom};
 use substrate_service::{Roles as ServiceRoles, ServiceFactory};
@@ -51,9 +52,12 @@ where
     .map(|_| ())
 }
 
+// `--chain` takes one of the built-in alternatives, or the path to a JSON spec (plain or raw)
+// or to a TOML network description, which `build-spec` turns into a JSON spec.
 fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
     Ok(match chain_spec::Alternative::from(id) {
         Some(spec) => Some(spec.load()?),
+        None if Path::new(id).is_file() => Some(chain_spec::load_file(Path::new(id))?),
         None => None,
     })
 }
@@ -5,6 +5,7 @@
 
 mod chain_spec;
 mod cli;
+mod reporter;
 mod service;
 
 pub use substrate_cli::{error, IntoExit, VersionInfo};
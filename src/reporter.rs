@@ -0,0 +1,170 @@
+//! Signs and submits the data space reports of an authority.
+//!
+//! The runtime exposes the checks of pending uploads and the encrypted answers of closed aggregate
+//! orders (`pending_checks`, `aggregate_inputs`). On every new best block the authority signs the checks
+//! and the aggregates of the orders encrypted to its aggregation key, and puts them into the transaction
+//! pool as unsigned extrinsics, which the runtime only accepts from its authorities.
+//!
+//! The aggregation key is an x25519 key derived from the authority key, the authority registers it on
+//! chain the first time it runs. The answers are only ever decrypted here, and the noise added to an
+//! aggregate is drawn from a seed only the authority knows.
+
+use std::sync::Arc;
+
+use data_space_runtime::data_space::{self, AggregateInput, AggregateReport, DataSpaceApi};
+use data_space_runtime::opaque::{Block, BlockId};
+use data_space_runtime::{AccountId, Balance, BlockNumber, Call, Hash, Runtime, UncheckedExtrinsic};
+use futures::{Future, Stream};
+use log::{debug, warn};
+use parity_codec::{Decode, Encode};
+use primitives::{blake2_256, ed25519::Pair, Pair as PairT};
+use substrate_client::{runtime_api::ProvideRuntimeApi, BlockchainEvents};
+use transaction_pool::txpool::{ChainApi, Pool};
+use x25519_dalek::{PublicKey, StaticSecret};
+
+// how many upload checks an authority reports per block
+const CHECKS_PER_BLOCK: u32 = 16;
+// the messages whose signatures seed the aggregation secret and the noise, ed25519 signatures are deterministic
+const AGGREGATION_SECRET_CONTEXT: &[u8] = b"dataspace/aggregation-secret";
+const NOISE_CONTEXT: &[u8] = b"dataspace/noise";
+
+// what an authority needs to compute aggregates
+struct Aggregator {
+    secret: StaticSecret,
+    public: [u8; 32],
+    noise_seed: [u8; 32],
+}
+
+impl Aggregator {
+    fn new(key: &Pair) -> Self {
+        let secret = StaticSecret::from(blake2_256(key.sign(AGGREGATION_SECRET_CONTEXT).as_ref()));
+        let public = *PublicKey::from(&secret).as_bytes();
+        Aggregator {
+            secret,
+            public,
+            noise_seed: blake2_256(key.sign(NOISE_CONTEXT).as_ref()),
+        }
+    }
+
+    // the value of an answer, see data_space::AggregateOrder for the format
+    fn decrypt(&self, input: &AggregateInput<AccountId>, person: &AccountId, answer: &[u8]) -> Option<u64> {
+        if answer.len() != data_space::ANSWER_LEN {
+            return None;
+        }
+        let mut ephemeral = [0u8; 32];
+        ephemeral.copy_from_slice(&answer[..32]);
+        let shared = self.secret.diffie_hellman(&PublicKey::from(ephemeral));
+        let k = blake2_256(&(shared.as_bytes(), &input.company, input.order_id, person).encode());
+        let tag = blake2_256(&[&k[..], &answer[32..40]].concat());
+        if tag[..16] != answer[40..] {
+            return None;
+        }
+        let mut value = [0u8; 8];
+        for (i, byte) in value.iter_mut().enumerate() {
+            *byte = answer[32 + i] ^ k[i];
+        }
+        Some(u64::from_le_bytes(value)).filter(|&value| value <= input.max_value)
+    }
+
+    // The noise only depends on the secret seed and the order: a report submitted again after it was
+    // dropped from the pool carries the same noise, so nobody can average several of them.
+    fn aggregate(&self, input: &AggregateInput<AccountId>) -> AggregateReport<AccountId> {
+        let mut values = Vec::new();
+        let mut rejected = Vec::new();
+        for (index, (person, answer)) in input.answers.iter().enumerate() {
+            match self.decrypt(input, person, answer) {
+                Some(value) => values.push(value),
+                None => rejected.push(index as u32),
+            }
+        }
+        let mut draws = 0u32;
+        let random = || {
+            draws += 1;
+            let bytes = blake2_256(&(self.noise_seed, &input.company, input.order_id, draws).encode());
+            u64::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7]])
+        };
+        let (mean, histogram) = data_space::noisy_aggregate(&values, input.max_value, &input.buckets, input.epsilon_millis, random);
+        AggregateReport {
+            company: input.company.clone(),
+            order_id: input.order_id,
+            mean,
+            histogram,
+            rejected,
+        }
+    }
+}
+
+/// Reports on every new best block until the node exits.
+pub fn start<C, A>(
+    client: Arc<C>,
+    pool: Arc<Pool<A>>,
+    key: Arc<Pair>,
+    on_exit: impl Future<Item = (), Error = ()>,
+) -> impl Future<Item = (), Error = ()>
+where
+    C: ProvideRuntimeApi + BlockchainEvents<Block>,
+    C::Api: DataSpaceApi<Block, AccountId, Balance, Hash, BlockNumber>,
+    A: ChainApi<Block = Block>,
+{
+    let aggregator = Aggregator::new(&key);
+    client
+        .import_notification_stream()
+        .filter(|notification| notification.is_new_best)
+        .for_each(move |notification| {
+            let at = BlockId::hash(notification.hash);
+            for call in reports(&*client, &at, &key, &aggregator) {
+                submit(&*pool, &at, call);
+            }
+            Ok(())
+        })
+        .select(on_exit)
+        .then(|_| Ok(()))
+}
+
+// the signed report calls for the state at a block
+fn reports<C>(client: &C, at: &BlockId, key: &Pair, aggregator: &Aggregator) -> Vec<data_space::Call<Runtime>>
+where
+    C: ProvideRuntimeApi,
+    C::Api: DataSpaceApi<Block, AccountId, Balance, Hash, BlockNumber>,
+{
+    let api = client.runtime_api();
+    let mut calls = Vec::new();
+    match api.pending_checks(at, CHECKS_PER_BLOCK) {
+        Ok(checks) => calls.extend(checks.into_iter().map(|check| {
+            let signature = key.sign(&check.payload());
+            data_space::Call::report_validation(check, key.public(), signature)
+        })),
+        Err(e) => warn!("Failed to read the pending upload checks: {:?}", e),
+    }
+    match api.is_aggregation_key(at, aggregator.public) {
+        Ok(true) => {},
+        Ok(false) => {
+            let signature = key.sign(&data_space::aggregation_key_payload(&aggregator.public));
+            calls.push(data_space::Call::register_aggregation_key(aggregator.public, key.public(), signature));
+        },
+        Err(e) => warn!("Failed to look up the aggregation key: {:?}", e),
+    }
+    match api.aggregate_inputs(at) {
+        Ok(inputs) => calls.extend(inputs.iter()
+            .filter(|input| input.encryption_key == aggregator.public)
+            .map(|input| {
+                let report = aggregator.aggregate(input);
+                let signature = key.sign(&report.payload());
+                data_space::Call::report_aggregate(report, key.public(), signature)
+            })),
+        Err(e) => warn!("Failed to read the aggregate answers: {:?}", e),
+    }
+    calls
+}
+
+fn submit<A: ChainApi<Block = Block>>(pool: &Pool<A>, at: &BlockId, call: data_space::Call<Runtime>) {
+    let xt = UncheckedExtrinsic::new_unsigned(Call::DataspaceModule(call));
+    let opaque = match Decode::decode(&mut &xt.encode()[..]) {
+        Some(opaque) => opaque,
+        None => return warn!("Failed to encode a report"),
+    };
+    // another authority may have reported the same thing already, the pool rejects the duplicate
+    if let Err(e) = pool.submit_one(at, opaque) {
+        debug!("Report not submitted: {:?}", e);
+    }
+}
@@ -2,6 +2,7 @@
 
 #![warn(unused_extern_crates)]
 
+use crate::reporter;
 use basic_authorship::ProposerFactory;
 use consensus::{import_queue, start_aura, AuraImportQueue, NothingExtra, SlotDuration};
 use data_space_runtime::{self, opaque::Block, GenesisConfig, RuntimeApi};
@@ -63,6 +64,12 @@ construct_service_factory! {
                         transaction_pool: service.transaction_pool(),
                         inherents_pool: service.inherents_pool(),
                     });
+                    executor.spawn(reporter::start(
+                        service.client(),
+                        service.transaction_pool(),
+                        key.clone(),
+                        service.on_exit(),
+                    ));
                     let client = service.client();
                     executor.spawn(start_aura(
                         SlotDuration::get_or_compute(&*client)?,